@@ -11,19 +11,25 @@ use core::hash::Hasher;
 pub const DEFAULT_POLY: u32 = 0x04C11DB7;
 ///Initial value for CRC's INIT
 pub const DEFAULT_INIT: u32 = 0xFFFF_FFFF;
+///Value, which CRC-32 (IEEE) result is XORed with
+pub const IEEE_XOR_OUT: u32 = 0xFFFF_FFFF;
+///Polynomial of CRC-16-CCITT
+pub const CCITT_POLY: u32 = 0x1021;
+///Initial value of CRC-16-CCITT
+pub const CCITT_INIT: u32 = 0xFFFF;
 
 #[derive(Copy, Clone)]
 #[repr(u8)]
 ///Describes possible polynomial sizes
 pub enum PolySize {
     ///Uses 32 bits
-    Word = 0x00,
+    Word = 0b00,
     ///Uses 16 bits
-    Half = 0x01,
+    Half = 0b01,
     ///Uses 8 bits
-    Byte = 0x10,
+    Byte = 0b10,
     ///Uses 7 bits
-    SevenBit = 0x11,
+    SevenBit = 0b11,
 }
 
 #[derive(Copy, Clone)]
@@ -37,20 +43,152 @@ pub enum PolySize {
 ///- 0xB23CD458 with bit-reversal done on the full word
 pub enum ReverseInput {
     ///Bit order remains the same
-    None = 0x00,
+    None = 0b00,
     ///Reversal byte by byte
-    Byte = 0x01,
+    Byte = 0b01,
     ///Reversal by 2 bytes
-    Half = 0x10,
+    Half = 0b10,
     ///Reversal by 4 bytes
-    Word = 0x11,
+    Word = 0b11,
+}
+
+#[derive(Copy, Clone)]
+///Configuration of CRC calculation, applied at once by `build`
+pub struct CrcConfig {
+    ///Polynomial
+    pub poly: u32,
+    ///Initial value
+    pub init: u32,
+    ///Polynomial size
+    pub poly_size: PolySize,
+    ///Reversal of input
+    pub reverse_in: ReverseInput,
+    ///Whether to reverse output
+    pub reverse_out: bool,
+    ///Value, which result is XORed with by `CRC::finalize`
+    pub xor_out: u32,
+}
+
+impl CrcConfig {
+    ///Configuration of CRC-32 (IEEE), as used by Ethernet and zlib.
+    pub fn ieee() -> Self {
+        Self {
+            poly: DEFAULT_POLY,
+            init: DEFAULT_INIT,
+            poly_size: PolySize::Word,
+            reverse_in: ReverseInput::Word,
+            reverse_out: true,
+            xor_out: IEEE_XOR_OUT,
+        }
+    }
+
+    ///Configuration of CRC-16-CCITT with initial value 0xFFFF.
+    pub fn ccitt() -> Self {
+        Self {
+            poly: CCITT_POLY,
+            init: CCITT_INIT,
+            poly_size: PolySize::Half,
+            reverse_in: ReverseInput::None,
+            reverse_out: false,
+            xor_out: 0,
+        }
+    }
+
+    ///Creates new instance of CRC calculator with this configuration.
+    ///
+    ///Takes ownership over device CRC
+    pub fn build(&self, inner: Inner) -> CRC {
+        let mut crc = CRC::new(inner);
+        crc.set_poly_size(self.poly_size)
+           .set_poly(self.poly)
+           .set_init(self.init)
+           .reverse_input(self.reverse_in)
+           .reverse_output(self.reverse_out)
+           .set_xor_out(self.xor_out);
+        crc.reset();
+        crc
+    }
+}
+
+impl Default for CrcConfig {
+    ///Configuration of CRC after reset
+    fn default() -> Self {
+        Self {
+            poly: DEFAULT_POLY,
+            init: DEFAULT_INIT,
+            poly_size: PolySize::Word,
+            reverse_in: ReverseInput::None,
+            reverse_out: false,
+            xor_out: 0,
+        }
+    }
+}
+
+///Piece of data, which is written into CRC at once
+enum Chunk {
+    Word(u32),
+    Half(u16),
+    Byte(u8),
+}
+
+impl ReverseInput {
+    fn from_bits(bits: u8) -> Self {
+        match bits {
+            0b00 => ReverseInput::None,
+            0b01 => ReverseInput::Byte,
+            0b10 => ReverseInput::Half,
+            _ => ReverseInput::Word,
+        }
+    }
+}
+
+///Splits `bytes` into the largest possible chunks, so that CRC processes bytes in their order.
+///
+///CRC processes chunk from its most significant bit, after bits are reversed within unit of `reverse`.
+///Therefore bytes are little-endian within unit of reversal, while units are big-endian.
+fn for_each_chunk<F: FnMut(Chunk)>(bytes: &[u8], reverse: ReverseInput, mut f: F) {
+    let mut bytes = bytes;
+
+    let half = |bytes: &[u8]| match reverse {
+        ReverseInput::Word | ReverseInput::Half => u16::from_le_bytes([bytes[0], bytes[1]]),
+        ReverseInput::Byte | ReverseInput::None => u16::from_be_bytes([bytes[0], bytes[1]]),
+    };
+    let word = |bytes: &[u8]| match reverse {
+        ReverseInput::Word => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        ReverseInput::Half => (u32::from(half(&bytes[..2])) << 16) | u32::from(half(&bytes[2..])),
+        ReverseInput::Byte | ReverseInput::None => u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+    };
+
+    loop {
+        match bytes.len() {
+            0 => break,
+            1 => {
+                f(Chunk::Byte(bytes[0]));
+                break;
+            },
+            2 => {
+                f(Chunk::Half(half(bytes)));
+                break;
+            }
+            3 => {
+                f(Chunk::Half(half(bytes)));
+                f(Chunk::Byte(bytes[2]));
+                break;
+            },
+            _ => {
+                f(Chunk::Word(word(bytes)));
+                bytes = &bytes[4..]
+            }
+        }
+    }
 }
 
 ///CRC module
 ///
 ///The default polynomial value is the CRC-32 (Ethernet) polynomial: 0x4C11DB7
 pub struct CRC {
-    inner: Inner
+    inner: Inner,
+    xor_out: u32,
 }
 
 impl CRC {
@@ -72,9 +210,24 @@ impl CRC {
     pub fn new(inner: Inner) -> Self {
         Self {
             inner,
+            xor_out: 0,
         }
     }
 
+    ///Creates new instance of CRC calculator, configured for CRC-32 (IEEE), as used by Ethernet and zlib.
+    ///
+    ///Use `finalize` or `checksum` to get standard value.
+    pub fn new_ieee(inner: Inner) -> Self {
+        CrcConfig::ieee().build(inner)
+    }
+
+    ///Creates new instance of CRC calculator, configured for CRC-16-CCITT with initial value 0xFFFF.
+    ///
+    ///Neither input nor output is reversed, use `checksum16` to get standard value.
+    pub fn new_ccitt(inner: Inner) -> Self {
+        CrcConfig::ccitt().build(inner)
+    }
+
     ///Sets new value to polynomial
     ///
     ///To obtain a reliable CRC calculation, the change on-fly of the polynomial value or size can
@@ -91,6 +244,14 @@ impl CRC {
         self
     }
 
+    ///Sets value, which result is XORed with by `finalize`
+    ///
+    ///CRC itself doesn't perform final XOR, therefore it is applied in software.
+    pub fn set_xor_out(&mut self, value: u32) -> &mut Self {
+        self.xor_out = value;
+        self
+    }
+
     ///Sets whether to reverse output
     pub fn reverse_output(&mut self, value: bool) -> &mut Self {
         self.inner.cr.modify(|_, w| w.rev_out().bit(value));
@@ -110,10 +271,26 @@ impl CRC {
     }
 
     ///Resets CRC
+    ///
+    ///Value of independent data register is kept.
     pub fn reset(&self) {
         self.inner.cr.modify(|_, w| w.reset().set_bit());
     }
 
+    ///Stores `value` in independent data register.
+    ///
+    ///IDR is not used in calculation and is not affected by `reset`, so it can hold
+    ///partial result of another calculation.
+    pub fn set_idr(&mut self, value: u32) {
+        // NOTE(unsafe) IDR is 32 bit wide, while device crate describes only 8 bits (see 14.4.2)
+        self.inner.idr.write(|w| unsafe { w.bits(value) });
+    }
+
+    ///Retrieves value of independent data register.
+    pub fn get_idr(&self) -> u32 {
+        self.inner.idr.read().bits()
+    }
+
     ///Retrieves current result
     pub fn result(&self) -> u32 {
         unsafe {
@@ -121,6 +298,46 @@ impl CRC {
         }
     }
 
+    ///Calculates CRC of `data` from initial value, with final XOR applied.
+    ///
+    ///CRC is reset beforehand, so any ongoing calculation is discarded.
+    ///Bytes are processed in their order with any reversal of input, see `Hasher::write`.
+    pub fn checksum(&mut self, data: &[u8]) -> u32 {
+        self.reset();
+        self.write(data);
+        self.finalize()
+    }
+
+    ///Feeds `data` word by word.
+    ///
+    ///Fastest way to process word aligned data, use `result` or `finalize` to get its CRC.
+    pub fn feed_words(&mut self, data: &[u32]) {
+        for word in data {
+            *self += *word;
+        }
+    }
+
+    ///Retrieves current result with final XOR applied
+    pub fn finalize(&self) -> u32 {
+        self.result() ^ self.xor_out
+    }
+
+    ///Calculates 16 bit CRC of `data` from initial value, with final XOR applied.
+    pub fn checksum16(&mut self, data: &[u8]) -> u16 {
+        self.checksum(data) as u16
+    }
+
+    ///Resets CRC and starts new calculation.
+    ///
+    ///Configuration cannot be changed while returned computation is alive, as required by reference.
+    ///Result remains readable by `result` after it is dropped.
+    pub fn start_new(&mut self) -> CrcComputation<'_> {
+        self.reset();
+        CrcComputation {
+            crc: self,
+        }
+    }
+
     ///Consumes self and returns device's CRC
     pub fn into_raw(self) -> Inner {
         self.inner
@@ -161,35 +378,14 @@ impl Hasher for CRC {
         self.result() as u64
     }
 
+    ///Feeds `bytes` in their order, regardless of configured reversal of input.
     fn write(&mut self, bytes: &[u8]) {
-        let mut bytes = bytes;
-
-        loop {
-            match bytes.len() {
-                0 => break,
-                1 => {
-                    *self += bytes[0];
-                    break;
-                },
-                2 => {
-                    *self += unsafe { *(&bytes as *const _ as *const u16) };
-                    break;
-                }
-                3 => {
-                    *self += unsafe { *(&bytes as *const _ as *const u16) };
-                    *self += bytes[2];
-                    break;
-                },
-                4 => {
-                    *self += unsafe { *(&bytes as *const _ as *const u32) };
-                    break;
-                },
-                _ => {
-                    *self += unsafe { *(&bytes as *const _ as *const u32) };
-                    bytes = &bytes[4..]
-                }
-            }
-        }
+        let reverse = ReverseInput::from_bits(self.inner.cr.read().rev_in().bits());
+        for_each_chunk(bytes, reverse, |chunk| match chunk {
+            Chunk::Word(value) => *self += value,
+            Chunk::Half(value) => *self += value,
+            Chunk::Byte(value) => *self += value,
+        });
     }
 
     #[inline]
@@ -207,3 +403,174 @@ impl Hasher for CRC {
         *self += value;
     }
 }
+
+///Ongoing CRC calculation, started by `CRC::start_new`
+pub struct CrcComputation<'a> {
+    crc: &'a mut CRC,
+}
+
+impl<'a> CrcComputation<'a> {
+    ///Retrieves current result
+    pub fn result(&self) -> u32 {
+        self.crc.result()
+    }
+
+    ///Retrieves current result with final XOR applied
+    pub fn finalize(&self) -> u32 {
+        self.crc.finalize()
+    }
+}
+
+impl<'a> ops::AddAssign<u32> for CrcComputation<'a> {
+    fn add_assign(&mut self, value: u32) {
+        *self.crc += value;
+    }
+}
+
+impl<'a> ops::AddAssign<u16> for CrcComputation<'a> {
+    fn add_assign(&mut self, value: u16) {
+        *self.crc += value;
+    }
+}
+
+impl<'a> ops::AddAssign<u8> for CrcComputation<'a> {
+    fn add_assign(&mut self, value: u8) {
+        *self.crc += value;
+    }
+}
+
+impl<'a> Hasher for CrcComputation<'a> {
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.crc.finish()
+    }
+
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        self.crc.write(bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ///Model of CRC, which reverses bits of input within written size at most
+    struct Model {
+        //CRC and polynomial are aligned to the most significant bit
+        crc: u32,
+        poly: u32,
+        size: u32,
+        config: CrcConfig,
+    }
+
+    impl Model {
+        fn new(config: CrcConfig) -> Self {
+            let size = match config.poly_size {
+                PolySize::Word => 32,
+                PolySize::Half => 16,
+                PolySize::Byte => 8,
+                PolySize::SevenBit => 7,
+            };
+            Self {
+                crc: config.init << (32 - size),
+                poly: config.poly << (32 - size),
+                size,
+                config,
+            }
+        }
+
+        fn feed(&mut self, value: u32, size: u32) {
+            let unit = match self.config.reverse_in {
+                ReverseInput::None => 0,
+                ReverseInput::Byte => 8,
+                ReverseInput::Half => 16,
+                ReverseInput::Word => 32,
+            };
+            let unit = core::cmp::min(unit, size);
+            let value = match unit {
+                0 => value,
+                _ => (0..size / unit).fold(0, |result, idx| {
+                    let offset = idx * unit;
+                    let bits = (value >> offset).reverse_bits() >> (32 - unit);
+                    result | (bits << offset)
+                }),
+            };
+
+            self.crc ^= value << (32 - size);
+            for _ in 0..size {
+                self.crc = match self.crc & 0x8000_0000 {
+                    0 => self.crc << 1,
+                    _ => (self.crc << 1) ^ self.poly,
+                };
+            }
+        }
+
+        fn result(&self) -> u32 {
+            let result = match self.config.reverse_out {
+                true => self.crc.reverse_bits(),
+                false => self.crc >> (32 - self.size),
+            };
+            result ^ self.config.xor_out
+        }
+    }
+
+    ///Software model of `CRC::checksum` of CRC built from `config`
+    fn model_checksum(config: CrcConfig, data: &[u8]) -> u32 {
+        let mut model = Model::new(config);
+        for_each_chunk(data, config.reverse_in, |chunk| match chunk {
+            Chunk::Word(value) => model.feed(value, 32),
+            Chunk::Half(value) => model.feed(value as u32, 16),
+            Chunk::Byte(value) => model.feed(value as u32, 8),
+        });
+        model.result()
+    }
+
+    #[test]
+    pub fn register_values() {
+        //CR fields are 2 bits wide
+        assert_eq!(PolySize::Word as u8, 0b00);
+        assert_eq!(PolySize::Half as u8, 0b01);
+        assert_eq!(PolySize::Byte as u8, 0b10);
+        assert_eq!(PolySize::SevenBit as u8, 0b11);
+        assert_eq!(ReverseInput::None as u8, 0b00);
+        assert_eq!(ReverseInput::Byte as u8, 0b01);
+        assert_eq!(ReverseInput::Half as u8, 0b10);
+        assert_eq!(ReverseInput::Word as u8, 0b11);
+
+        let ieee = CrcConfig::ieee();
+        assert_eq!(ieee.poly_size as u8, 0b00);
+        assert_eq!(ieee.reverse_in as u8, 0b11);
+        assert!(ieee.reverse_out);
+
+        let ccitt = CrcConfig::ccitt();
+        assert_eq!(ccitt.poly_size as u8, 0b01);
+        assert_eq!(ccitt.reverse_in as u8, 0b00);
+        assert!(!ccitt.reverse_out);
+    }
+
+    #[test]
+    pub fn chunks_of_data() {
+        let ieee = CrcConfig::ieee();
+        //CRC-32 (IEEE) check value and data of any length, regardless of alignment
+        assert_eq!(model_checksum(ieee, b"123456789"), 0xCBF43926);
+        assert_eq!(model_checksum(ieee, &b"_123456789"[1..]), 0xCBF43926);
+        assert_eq!(model_checksum(ieee, b"a"), 0xE8B7BE43);
+        assert_eq!(model_checksum(ieee, b"abc"), 0x352441C2);
+        assert_eq!(model_checksum(ieee, b""), 0);
+
+        //Bytes are fed in their order with any reversal of input
+        let by_byte = CrcConfig { reverse_in: ReverseInput::Byte, ..ieee };
+        assert_eq!(model_checksum(by_byte, b"123456789"), 0xCBF43926);
+        let by_half = CrcConfig { reverse_in: ReverseInput::Half, ..ieee };
+        assert_eq!(model_checksum(by_half, b"123456789"), 0xCBF43926);
+    }
+
+    #[test]
+    pub fn ccitt_check_value() {
+        let ccitt = CrcConfig::ccitt();
+        assert_eq!(model_checksum(ccitt, b"123456789") as u16, 0x29B1);
+        assert_eq!(model_checksum(ccitt, b"A") as u16, 0xB915);
+        assert_eq!(model_checksum(ccitt, b"") as u16, 0xFFFF);
+    }
+}
@@ -0,0 +1,175 @@
+//! Clock recovery system
+//!
+//! CRS trims HSI48 against a synchronization signal, keeping it precise enough for USB without
+//! a crystal. Typically USB SOF packets, sent by host each millisecond, are used for that.
+//!
+//! HSI48 must be turned on beforehand, see `rcc::clocking::Clk48ClkSource::HSI48`.
+//!
+//! Reference Ch. 7 Clock recovery system
+
+use core::ptr;
+
+use crate::rcc::APB1;
+
+//CRS is not described by the device crate (see 7.7)
+const CRS_BASE: usize = 0x4000_6000;
+const CR_OFFSET: usize = 0x00;
+const CFGR_OFFSET: usize = 0x04;
+const ISR_OFFSET: usize = 0x08;
+const ICR_OFFSET: usize = 0x0C;
+
+//CRSEN of APB1ENR1, not described by the device crate (see 6.4.19)
+const CRSEN: u32 = 1 << 24;
+
+//CR bits
+const CEN: u32 = 1 << 5;
+const AUTOTRIMEN: u32 = 1 << 6;
+const TRIM_OFFSET: u8 = 8;
+const TRIM_MASK: u32 = 0x3f;
+
+//CFGR fields
+const FELIM_OFFSET: u8 = 16;
+const SYNCSRC_OFFSET: u8 = 28;
+
+//ISR flags, ICR uses the same positions for the first four
+const SYNCOKF: u32 = 1;
+const SYNCWARNF: u32 = 1 << 1;
+const ERRF: u32 = 1 << 2;
+const ESYNCF: u32 = 1 << 3;
+const SYNCERR: u32 = 1 << 8;
+const SYNCMISS: u32 = 1 << 9;
+const TRIMOVF: u32 = 1 << 10;
+
+#[derive(Copy, Clone)]
+#[repr(u8)]
+/// Source of synchronization signal
+pub enum SyncSource {
+    /// CRS_SYNC pin, which must be configured separately
+    GPIO = 0b00,
+    /// LSE oscillator
+    LSE = 0b01,
+    /// USB SOF packets
+    USB = 0b10,
+}
+
+#[derive(Copy, Clone)]
+/// CRS configuration
+pub struct CrsConfig {
+    /// Source of synchronization signal
+    pub source: SyncSource,
+    /// Value loaded into counter on each synchronization event
+    ///
+    /// It is the ratio of target 48 MHz to frequency of synchronization signal, minus 1.
+    pub reload: u16,
+    /// Frequency error limit, counter steps beyond which trimming is not possible
+    pub error_limit: u8,
+}
+
+impl CrsConfig {
+    /// Configuration for synchronization with 1 kHz USB SOF, as recommended by reference.
+    pub fn usb_sof() -> Self {
+        Self {
+            source: SyncSource::USB,
+            reload: 47_999,
+            error_limit: 34,
+        }
+    }
+}
+
+impl Default for CrsConfig {
+    fn default() -> Self {
+        Self::usb_sof()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+/// Errors of synchronization, at which trimming stops
+pub enum CrsError {
+    /// Frequency error is beyond 128 times of error limit
+    SyncError,
+    /// Synchronization event has not been received
+    SyncMissed,
+    /// Trimming value reached its limit
+    TrimOverflow,
+}
+
+/// Clock recovery system, trimming HSI48 automatically
+pub struct Crs(());
+
+impl Crs {
+    /// Enables CRS clock on APB1 and starts automatic trimming of HSI48 with `config`.
+    pub fn new(apb1: &mut APB1, config: CrsConfig) -> Self {
+        apb1.enr1().modify(|r, w| unsafe { w.bits(r.bits() | CRSEN) });
+
+        // NOTE(unsafe) CRS registers are owned by this instance, CFGR can be written only while counter is disabled
+        unsafe {
+            Self::modify(CR_OFFSET, |cr| cr & !(CEN | AUTOTRIMEN));
+            let cfgr = (config.reload as u32) | ((config.error_limit as u32) << FELIM_OFFSET) | ((config.source as u32) << SYNCSRC_OFFSET);
+            ptr::write_volatile(Self::reg(CFGR_OFFSET), cfgr);
+            Self::modify(CR_OFFSET, |cr| cr | CEN | AUTOTRIMEN);
+        }
+
+        Crs(())
+    }
+
+    /// Stops trimming and disables CRS clock on APB1.
+    pub fn disable(self, apb1: &mut APB1) {
+        unsafe { Self::modify(CR_OFFSET, |cr| cr & !(CEN | AUTOTRIMEN)) };
+        apb1.enr1().modify(|r, w| unsafe { w.bits(r.bits() & !CRSEN) });
+    }
+
+    /// Retrieves current trimming value of HSI48.
+    pub fn trim(&self) -> u8 {
+        ((self.cr() >> TRIM_OFFSET) & TRIM_MASK) as u8
+    }
+
+    /// Returns whether HSI48 has been synchronized within error limit.
+    pub fn is_sync_ok(&self) -> bool {
+        self.isr() & SYNCOKF != 0
+    }
+
+    /// Returns whether frequency error exceeded error limit, requiring trimming.
+    pub fn is_sync_warning(&self) -> bool {
+        self.isr() & SYNCWARNF != 0
+    }
+
+    /// Returns whether synchronization event has been received after counter reached its end.
+    pub fn is_expected_sync(&self) -> bool {
+        self.isr() & ESYNCF != 0
+    }
+
+    /// Returns error of synchronization, if any.
+    pub fn error(&self) -> Option<CrsError> {
+        let isr = self.isr();
+        match isr & ERRF {
+            0 => None,
+            _ if isr & SYNCERR != 0 => Some(CrsError::SyncError),
+            _ if isr & SYNCMISS != 0 => Some(CrsError::SyncMissed),
+            _ if isr & TRIMOVF != 0 => Some(CrsError::TrimOverflow),
+            _ => None,
+        }
+    }
+
+    /// Clears all flags, including errors.
+    pub fn clear_flags(&mut self) {
+        // NOTE(unsafe) Writing 0 to ICR has no effect
+        unsafe { ptr::write_volatile(Self::reg(ICR_OFFSET), SYNCOKF | SYNCWARNF | ERRF | ESYNCF) };
+    }
+
+    fn isr(&self) -> u32 {
+        unsafe { ptr::read_volatile(Self::reg(ISR_OFFSET)) }
+    }
+
+    fn cr(&self) -> u32 {
+        unsafe { ptr::read_volatile(Self::reg(CR_OFFSET)) }
+    }
+
+    fn reg(offset: usize) -> *mut u32 {
+        (CRS_BASE + offset) as *mut u32
+    }
+
+    unsafe fn modify<F: FnOnce(u32) -> u32>(offset: usize, f: F) {
+        let reg = Self::reg(offset);
+        ptr::write_volatile(reg, f(ptr::read_volatile(reg)));
+    }
+}
@@ -1,8 +1,151 @@
 //! Flash memory
 
+use core::ops::RangeInclusive;
+use core::ptr;
+
+use cortex_m::asm;
 use stm32l4x6::{flash, FLASH};
 
 use crate::common::Constrain;
+use crate::power::VoltageRange;
+use crate::signature;
+use crate::time::Hertz;
+
+/// Address at which flash memory starts
+pub const FLASH_BASE: usize = 0x0800_0000;
+/// Size of flash page, the smallest unit of erase
+pub const PAGE_SIZE: usize = 2048;
+
+//Reference Ch. 3.3.5
+const KEY1: u32 = 0x4567_0123;
+const KEY2: u32 = 0xCDEF_89AB;
+
+//Reference Ch. 3.4.2
+const OPTKEY1: u32 = 0x0819_2A3B;
+const OPTKEY2: u32 = 0x4C5D_6E7F;
+
+//Error flags of SR
+const OPERR: u32 = 1 << 1;
+const PROGERR: u32 = 1 << 3;
+const WRPERR: u32 = 1 << 4;
+const PGAERR: u32 = 1 << 5;
+const SIZERR: u32 = 1 << 6;
+const PGSERR: u32 = 1 << 7;
+const MISERR: u32 = 1 << 8;
+const FASTERR: u32 = 1 << 9;
+const RDERR: u32 = 1 << 14;
+const OPTVERR: u32 = 1 << 15;
+const SR_ERRORS: u32 = OPERR | PROGERR | WRPERR | PGAERR | SIZERR | PGSERR | MISERR | FASTERR | RDERR | OPTVERR;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+/// Errors of flash programming and erase
+pub enum FlashError {
+    /// Flash is locked, or unlock sequence failed, in which case flash is locked until reset
+    Locked,
+    /// Address is outside of flash
+    InvalidAddress,
+    /// Address is not aligned to double-word, or page when erasing
+    Alignment,
+    /// Double-word to program is not erased
+    Programming,
+    /// Address is write protected
+    WriteProtection,
+    /// Error of programming sequence or size reported by hardware
+    Sequence,
+    /// Other error of operation reported by hardware
+    Operation,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+/// Read protection level (see 3.5.1)
+pub enum RdpLevel {
+    /// No protection
+    Level0,
+    /// Flash can't be read by debugger or code in RAM, regression to level 0 erases flash
+    Level1,
+    /// Debug is disabled, option bytes are frozen and level cannot be changed anymore
+    Level2,
+}
+
+impl RdpLevel {
+    /// Returns level of RDP option byte value
+    pub fn from_bits(rdp: u8) -> Self {
+        match rdp {
+            0xAA => RdpLevel::Level0,
+            0xCC => RdpLevel::Level2,
+            _ => RdpLevel::Level1,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+/// Bank of flash memory
+///
+/// Devices with 1 MB of flash always have two banks of 512 KB.
+/// Devices with 512 KB or 256 KB have two banks only when DUALBANK option bit is set,
+/// otherwise whole flash is a single bank 1.
+pub enum Bank {
+    /// Bank 1, starting at `FLASH_BASE`
+    Bank1,
+    /// Bank 2, following bank 1
+    Bank2,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+/// Write protection area, each bank has two of them
+pub enum WrpArea {
+    /// Area A
+    A,
+    /// Area B
+    B,
+}
+
+//WRPxy_STRT and WRPxy_END of WRPxyR
+const WRP_END_OFFSET: u8 = 16;
+const WRP_MASK: u32 = 0xFF | (0xFF << WRP_END_OFFSET);
+
+/// Returns range of pages write protected by WRPxyR `bits`
+fn wrp_range(bits: u32) -> Option<RangeInclusive<u8>> {
+    let start = bits as u8;
+    let end = (bits >> WRP_END_OFFSET) as u8;
+    match start <= end {
+        true => Some(start..=end),
+        false => None,
+    }
+}
+
+/// Returns WRPxyR `bits` with area set to `pages`
+fn wrp_bits(bits: u32, pages: Option<RangeInclusive<u8>>) -> u32 {
+    // Area is disabled, when its start is above its end
+    let (start, end) = match pages {
+        Some(pages) => (*pages.start(), *pages.end()),
+        None => (0xFF, 0x00),
+    };
+    (bits & !WRP_MASK) | start as u32 | ((end as u32) << WRP_END_OFFSET)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+/// Raw values of option bytes, as loaded into option registers (see 3.4.1)
+pub struct OptionBytes {
+    /// User options and read protection level (OPTR)
+    pub optr: u32,
+    /// Start of PCROP area of bank 1 (PCROP1SR)
+    pub pcrop1sr: u32,
+    /// End of PCROP area of bank 1 and PCROP_RDP (PCROP1ER)
+    pub pcrop1er: u32,
+    /// Write protection area A of bank 1 (WRP1AR)
+    pub wrp1ar: u32,
+    /// Write protection area B of bank 1 (WRP1BR)
+    pub wrp1br: u32,
+    /// Start of PCROP area of bank 2 (PCROP2SR)
+    pub pcrop2sr: u32,
+    /// End of PCROP area of bank 2 (PCROP2ER)
+    pub pcrop2er: u32,
+    /// Write protection area A of bank 2 (WRP2AR)
+    pub wrp2ar: u32,
+    /// Write protection area B of bank 2 (WRP2BR)
+    pub wrp2br: u32,
+}
 
 impl Constrain<Parts> for FLASH {
     fn constrain(self) -> Parts {
@@ -16,10 +159,438 @@ pub struct Parts {
     pub acr: ACR,
 }
 
+impl Parts {
+    /// Unlocks flash for program and erase.
+    ///
+    /// Returns `FlashError::Locked` if flash stays locked, which happens when unlock sequence
+    /// has been violated since reset.
+    pub fn unlock(&mut self) -> Result<(), FlashError> {
+        let flash = unsafe { &(*FLASH::ptr()) };
+        if flash.cr.read().lock().bit_is_set() {
+            flash.keyr.write(|w| unsafe { w.keyr().bits(KEY1) });
+            flash.keyr.write(|w| unsafe { w.keyr().bits(KEY2) });
+        }
+
+        match flash.cr.read().lock().bit_is_set() {
+            true => Err(FlashError::Locked),
+            false => Ok(()),
+        }
+    }
+
+    /// Locks flash, protecting it against program and erase.
+    pub fn lock(&mut self) {
+        unsafe { (*FLASH::ptr()).cr.modify(|_, w| w.lock().set_bit()) };
+    }
+
+    /// Returns bank and number of page within the bank, which contains `addr`.
+    ///
+    /// Pages are numbered from 0 within each bank, e.g. on 1 MB devices page at 0x0808_0000 is page 0 of bank 2.
+    /// Addresses are assumed to be not swapped by BFB2, i.e. SYSCFG's FB_MODE is not set.
+    pub fn page_of(&self, addr: usize) -> Result<(Bank, u8), FlashError> {
+        let offset = Self::offset(addr, 1)?;
+        let bank_size = bank_size();
+        let bank = match offset >= bank_size {
+            true => Bank::Bank2,
+            false => Bank::Bank1,
+        };
+        Ok((bank, ((offset % bank_size) / PAGE_SIZE) as u8))
+    }
+
+    /// Erases page starting at `addr`, which must be aligned to `PAGE_SIZE`.
+    ///
+    /// See `page_of` for mapping of address to bank and `erase_bank_page` for details.
+    pub fn erase_page(&mut self, addr: usize) -> Result<(), FlashError> {
+        let _offset = Self::offset(addr, PAGE_SIZE)?;
+        let (bank, page) = self.page_of(addr)?;
+        self.erase_bank_page(bank, page)
+    }
+
+    /// Erases `page` of `bank`.
+    ///
+    /// Code can keep executing from the other bank, while access to the bank being erased stalls until erase ends,
+    /// so code and interrupt handlers that must not stall should run from the other bank or RAM.
+    /// Afterwards data read through cache may be outdated, see `ACR::reset_data_cache`.
+    ///
+    /// See Reference manual Ch. 3.3.6
+    pub fn erase_bank_page(&mut self, bank: Bank, page: u8) -> Result<(), FlashError> {
+        if (page as usize + 1) * PAGE_SIZE > bank_size() {
+            return Err(FlashError::InvalidAddress);
+        }
+        let is_bank2 = bank == Bank::Bank2;
+
+        let flash = unsafe { &(*FLASH::ptr()) };
+        Self::prepare(flash)?;
+        flash.cr.modify(|_, w| unsafe { w.per().set_bit().bker().bit(is_bank2).pnb().bits(page) });
+        flash.cr.modify(|_, w| w.start().set_bit());
+        let result = Self::finish(flash);
+        flash.cr.modify(|_, w| w.per().clear_bit());
+
+        result
+    }
+
+    /// Programs `data` into flash starting at `addr`, which must be aligned to double-word.
+    ///
+    /// Each double-word must be erased beforehand, see `erase_page`.
+    /// Afterwards data read through cache may be outdated, see `ACR::reset_data_cache`.
+    ///
+    /// See Reference manual Ch. 3.3.7
+    pub fn write(&mut self, addr: usize, data: &[u64]) -> Result<(), FlashError> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let _offset = Self::offset(addr, 8)?;
+        let _end = Self::offset(addr + data.len() * 8 - 8, 8)?;
+
+        let flash = unsafe { &(*FLASH::ptr()) };
+        Self::prepare(flash)?;
+        flash.cr.modify(|_, w| w.pg().set_bit());
+
+        let mut result = Ok(());
+        for (idx, &word) in data.iter().enumerate() {
+            let dst = (addr + idx * 8) as *mut u32;
+            // NOTE(unsafe) Double-word is programmed by two consecutive writes of words
+            unsafe {
+                ptr::write_volatile(dst, word as u32);
+                ptr::write_volatile(dst.add(1), (word >> 32) as u32);
+            }
+            result = Self::finish(flash);
+            if result.is_err() {
+                break;
+            }
+        }
+
+        flash.cr.modify(|_, w| w.pg().clear_bit());
+        result
+    }
+
+    /// Returns option bytes, as they have been loaded on reset.
+    pub fn option_bytes(&self) -> OptionBytes {
+        let flash = unsafe { &(*FLASH::ptr()) };
+        OptionBytes {
+            optr: flash.optr.read().bits(),
+            pcrop1sr: flash.pcrop1sr.read().bits(),
+            pcrop1er: flash.pcrop1er.read().bits(),
+            wrp1ar: flash.wrp1ar.read().bits(),
+            wrp1br: flash.wrp1br.read().bits(),
+            pcrop2sr: flash.pcrop2sr.read().bits(),
+            pcrop2er: flash.pcrop2er.read().bits(),
+            wrp2ar: flash.wrp2ar.read().bits(),
+            wrp2br: flash.wrp2br.read().bits(),
+        }
+    }
+
+    /// Unlocks option bytes for programming, flash must be unlocked first by `unlock`.
+    ///
+    /// Returns `FlashError::Locked` if option bytes stay locked.
+    pub fn unlock_options(&mut self) -> Result<(), FlashError> {
+        let flash = unsafe { &(*FLASH::ptr()) };
+        if flash.cr.read().optlock().bit_is_set() {
+            flash.optkeyr.write(|w| unsafe { w.optkeyr().bits(OPTKEY1) });
+            flash.optkeyr.write(|w| unsafe { w.optkeyr().bits(OPTKEY2) });
+        }
+
+        match flash.cr.read().optlock().bit_is_set() {
+            true => Err(FlashError::Locked),
+            false => Ok(()),
+        }
+    }
+
+    /// Locks option bytes, protecting them against programming.
+    pub fn lock_options(&mut self) {
+        unsafe { (*FLASH::ptr()).cr.modify(|_, w| w.optlock().set_bit()) };
+    }
+
+    /// Programs `options` into option bytes, which become effective after `launch_option_bytes` or power-on reset.
+    ///
+    /// Option bytes must be unlocked by `unlock_options`.
+    ///
+    /// # Safety
+    ///
+    /// Wrong values may make device unusable: RDP level 2 (0xCC) permanently disables debug
+    /// and option bytes programming, while lowering RDP from level 1 erases whole flash.
+    /// Options such as nBOOT0 and watchdog or reset configuration affect boot of the device.
+    ///
+    /// See Reference manual Ch. 3.4.2
+    pub unsafe fn program_option_bytes(&mut self, options: &OptionBytes) -> Result<(), FlashError> {
+        let flash = &(*FLASH::ptr());
+        if flash.cr.read().optlock().bit_is_set() {
+            return Err(FlashError::Locked);
+        }
+        Self::prepare(flash)?;
+
+        flash.optr.write(|w| w.bits(options.optr));
+        flash.pcrop1sr.write(|w| w.bits(options.pcrop1sr));
+        flash.pcrop1er.write(|w| w.bits(options.pcrop1er));
+        flash.wrp1ar.write(|w| w.bits(options.wrp1ar));
+        flash.wrp1br.write(|w| w.bits(options.wrp1br));
+        flash.pcrop2sr.write(|w| w.bits(options.pcrop2sr));
+        flash.pcrop2er.write(|w| w.bits(options.pcrop2er));
+        flash.wrp2ar.write(|w| w.bits(options.wrp2ar));
+        flash.wrp2br.write(|w| w.bits(options.wrp2br));
+
+        flash.cr.modify(|_, w| w.optstrt().set_bit());
+        Self::finish(flash)
+    }
+
+    /// Reloads option bytes, which resets the device.
+    pub fn launch_option_bytes(&mut self) -> ! {
+        unsafe { (*FLASH::ptr()).cr.modify(|_, w| w.obl_launch().set_bit()) };
+        // Reset is generated right away
+        loop {
+            asm::nop();
+        }
+    }
+
+    /// Returns current read protection level.
+    pub fn rdp_level(&self) -> RdpLevel {
+        RdpLevel::from_bits(unsafe { (*FLASH::ptr()).optr.read().rdp().bits() })
+    }
+
+    /// Returns whether debug is disabled permanently by read protection level 2.
+    pub fn is_debug_disabled(&self) -> bool {
+        self.rdp_level() == RdpLevel::Level2
+    }
+
+    /// Returns bank from which device boots, as selected by BFB2 option bit.
+    ///
+    /// When bank 2 is selected, banks are swapped in memory map after boot.
+    pub fn boot_bank(&self) -> Bank {
+        match unsafe { (*FLASH::ptr()).optr.read().bfb2().bit_is_set() } {
+            true => Bank::Bank2,
+            false => Bank::Bank1,
+        }
+    }
+
+    /// Selects bank from which device boots by programming BFB2 option bit.
+    ///
+    /// Selection becomes effective after `launch_option_bytes`.
+    /// Option bytes must be unlocked by `unlock_options`.
+    ///
+    /// # Safety
+    ///
+    /// When bank 2 is selected, but contains no valid vector table, device boots from bank 1, unless nBOOT0 option
+    /// selects boot from system memory. See `program_option_bytes`.
+    ///
+    /// See Reference manual Ch. 3.4.1
+    pub unsafe fn set_boot_bank(&mut self, bank: Bank) -> Result<(), FlashError> {
+        //BFB2 of OPTR
+        const BFB2: u32 = 1 << 20;
+
+        let mut options = self.option_bytes();
+        options.optr = match bank {
+            Bank::Bank1 => options.optr & !BFB2,
+            Bank::Bank2 => options.optr | BFB2,
+        };
+        self.program_option_bytes(&options)
+    }
+
+    /// Returns range of pages of `bank`, which are write protected by `area`, if it is enabled.
+    pub fn write_protection(&self, bank: Bank, area: WrpArea) -> Option<RangeInclusive<u8>> {
+        let options = self.option_bytes();
+        wrp_range(match (bank, area) {
+            (Bank::Bank1, WrpArea::A) => options.wrp1ar,
+            (Bank::Bank1, WrpArea::B) => options.wrp1br,
+            (Bank::Bank2, WrpArea::A) => options.wrp2ar,
+            (Bank::Bank2, WrpArea::B) => options.wrp2br,
+        })
+    }
+
+    /// Sets range of pages of `bank`, which are write protected by `area`, or disables the area with `None`.
+    ///
+    /// Page numbers are the same as in `page_of`. Protection becomes effective after `launch_option_bytes`.
+    /// Option bytes must be unlocked by `unlock_options`.
+    ///
+    /// See Reference manual Ch. 3.5.2
+    pub fn set_write_protection(&mut self, bank: Bank, area: WrpArea, pages: Option<RangeInclusive<u8>>) -> Result<(), FlashError> {
+        let mut options = self.option_bytes();
+        {
+            let wrp = match (bank, area) {
+                (Bank::Bank1, WrpArea::A) => &mut options.wrp1ar,
+                (Bank::Bank1, WrpArea::B) => &mut options.wrp1br,
+                (Bank::Bank2, WrpArea::A) => &mut options.wrp2ar,
+                (Bank::Bank2, WrpArea::B) => &mut options.wrp2br,
+            };
+            *wrp = wrp_bits(*wrp, pages);
+        }
+        // NOTE(unsafe) Only write protection is changed, which can be removed again
+        unsafe { self.program_option_bytes(&options) }
+    }
+
+    /// Returns offset of `addr` within flash, checking that it is aligned to `align`.
+    fn offset(addr: usize, align: usize) -> Result<usize, FlashError> {
+        if addr < FLASH_BASE || addr >= FLASH_BASE + flash_size() {
+            return Err(FlashError::InvalidAddress);
+        }
+        match addr % align {
+            0 => Ok(addr - FLASH_BASE),
+            _ => Err(FlashError::Alignment),
+        }
+    }
+
+    /// Waits for previous operation and clears its flags.
+    fn prepare(flash: &flash::RegisterBlock) -> Result<(), FlashError> {
+        if flash.cr.read().lock().bit_is_set() {
+            return Err(FlashError::Locked);
+        }
+        while flash.sr.read().bsy().bit_is_set() {}
+        flash.sr.write(|w| unsafe { w.bits(SR_ERRORS | 1) });
+        Ok(())
+    }
+
+    /// Waits for the operation to end and checks its errors.
+    fn finish(flash: &flash::RegisterBlock) -> Result<(), FlashError> {
+        while flash.sr.read().bsy().bit_is_set() {}
+
+        let sr = flash.sr.read().bits();
+        match sr & SR_ERRORS {
+            0 => Ok(()),
+            errors if errors & PGAERR != 0 => Err(FlashError::Alignment),
+            errors if errors & PROGERR != 0 => Err(FlashError::Programming),
+            errors if errors & WRPERR != 0 => Err(FlashError::WriteProtection),
+            errors if errors & (PGSERR | SIZERR) != 0 => Err(FlashError::Sequence),
+            _ => Err(FlashError::Operation),
+        }
+    }
+}
+
+/// Returns size of flash bank in bytes
+fn bank_size() -> usize {
+    let size = flash_size();
+    let is_dual_bank = size == 1024 * 1024 || unsafe { (*FLASH::ptr()).optr.read().dualbank().bit_is_set() };
+    match is_dual_bank {
+        true => size / 2,
+        false => size,
+    }
+}
+
+/// Returns size of flash in bytes
+fn flash_size() -> usize {
+    signature::flash_size_kb() as usize * 1024
+}
+
 /// Opaque ACR register
 pub struct ACR(());
 impl ACR {
     pub fn acr(&mut self) -> &flash::ACR {
         unsafe { &(*FLASH::ptr()).acr }
     }
+
+    /// Returns number of wait states, which flash requires at `sysclk` in voltage `range`.
+    ///
+    /// See Reference manual Ch. 3.3.3
+    pub fn latency_for(sysclk: Hertz, range: VoltageRange) -> u8 {
+        match range {
+            VoltageRange::Range1 => match sysclk.0 {
+                0...16_000_000 => 0,
+                16_000_001...32_000_000 => 1,
+                32_000_001...48_000_000 => 2,
+                48_000_001...64_000_000 => 3,
+                _ => 4,
+            },
+            VoltageRange::Range2 => match sysclk.0 {
+                0...6_000_000 => 0,
+                6_000_001...12_000_000 => 1,
+                12_000_001...18_000_000 => 2,
+                18_000_001...26_000_000 => 3,
+                _ => 4,
+            },
+        }
+    }
+
+    /// Sets number of wait states required at `sysclk` in voltage `range`, returning it.
+    ///
+    /// When SYSCLK is changed, it needs to be called before increasing it, or after decreasing it.
+    pub fn set_latency_for(&mut self, sysclk: Hertz, range: VoltageRange) -> u8 {
+        let latency = Self::latency_for(sysclk, range);
+        self.acr().modify(|_, w| unsafe { w.latency().bits(latency) });
+        // New value must be read back before accessing flash at higher frequency
+        while self.latency() != latency {}
+        latency
+    }
+
+    /// Returns current number of wait states.
+    pub fn latency(&mut self) -> u8 {
+        self.acr().read().latency().bits()
+    }
+
+    /// Enables prefetch buffer, which reads next instructions in advance,
+    /// reducing impact of flash wait states on sequential code.
+    ///
+    /// See Reference manual Ch. 3.3.3
+    pub fn enable_prefetch(&mut self) {
+        self.acr().modify(|_, w| w.prften().set_bit());
+    }
+
+    /// Disables prefetch buffer.
+    pub fn disable_prefetch(&mut self) {
+        self.acr().modify(|_, w| w.prften().clear_bit());
+    }
+
+    /// Enables instruction cache, which is enabled also by `CFGR::freeze`.
+    ///
+    /// See Reference manual Ch. 3.3.4
+    pub fn enable_instruction_cache(&mut self) {
+        self.acr().modify(|_, w| w.icen().set_bit());
+    }
+
+    /// Disables instruction cache.
+    pub fn disable_instruction_cache(&mut self) {
+        self.acr().modify(|_, w| w.icen().clear_bit());
+    }
+
+    /// Invalidates content of instruction cache, which is needed after code in flash has been changed.
+    ///
+    /// Cache is disabled during reset and enabled back, if it has been enabled.
+    pub fn reset_instruction_cache(&mut self) {
+        let is_enabled = self.acr().read().icen().bit_is_set();
+        self.acr().modify(|_, w| w.icen().clear_bit());
+        self.acr().modify(|_, w| w.icrst().set_bit());
+        self.acr().modify(|_, w| w.icrst().clear_bit().icen().bit(is_enabled));
+    }
+
+    /// Enables data cache, which caches literal pools and constants read from flash.
+    ///
+    /// See Reference manual Ch. 3.3.4
+    pub fn enable_data_cache(&mut self) {
+        self.acr().modify(|_, w| w.dcen().set_bit());
+    }
+
+    /// Disables data cache.
+    pub fn disable_data_cache(&mut self) {
+        self.acr().modify(|_, w| w.dcen().clear_bit());
+    }
+
+    /// Invalidates content of data cache, which is needed after flash has been programmed or erased.
+    ///
+    /// Cache is disabled during reset and enabled back, if it has been enabled.
+    pub fn reset_data_cache(&mut self) {
+        let is_enabled = self.acr().read().dcen().bit_is_set();
+        self.acr().modify(|_, w| w.dcen().clear_bit());
+        self.acr().modify(|_, w| w.dcrst().set_bit());
+        self.acr().modify(|_, w| w.dcrst().clear_bit().dcen().bit(is_enabled));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn rdp_level_from_bits() {
+        assert_eq!(RdpLevel::from_bits(0xAA), RdpLevel::Level0);
+        assert_eq!(RdpLevel::from_bits(0xBB), RdpLevel::Level1);
+        assert_eq!(RdpLevel::from_bits(0x00), RdpLevel::Level1);
+        assert_eq!(RdpLevel::from_bits(0xCC), RdpLevel::Level2);
+    }
+
+    #[test]
+    pub fn wrp_range_bits() {
+        // Reserved bits are kept
+        let reserved = 0xFF00_FF00;
+        assert_eq!(wrp_bits(reserved, Some(0..=15)), reserved | 0x000F_0000);
+        assert_eq!(wrp_range(wrp_bits(reserved, Some(0..=15))), Some(0..=15));
+        assert_eq!(wrp_range(wrp_bits(reserved, Some(7..=7))), Some(7..=7));
+        assert_eq!(wrp_bits(reserved, None), reserved | 0xFF);
+        assert_eq!(wrp_range(wrp_bits(0, None)), None);
+    }
 }
@@ -4,13 +4,19 @@
 //! enabling features, you can use definitions for specific chips which may include additional
 //! GPIO lines. In that case, you will probably not want to `use` this module directly, but instead
 //! use it re-exported by the chip module.
+//!
+//! Ports D and E, that are present on all packages with 100 pins and more, can be enabled
+//! without chip specific definitions via "GPIO_DE" feature.
 
 use core::marker::PhantomData;
 use core::ops::Deref;
 
-use embedded_hal::digital::{toggleable, OutputPin, StatefulOutputPin, InputPin};
+use embedded_hal::digital::{toggleable, OutputPin, StatefulOutputPin, ToggleableOutputPin, InputPin};
 
 use crate::rcc::AHB;
+use crate::syscfg::Syscfg;
+
+use stm32l4x6::EXTI;
 
 /// Input Mode Trait
 /// Implemented only for corresponding structs.
@@ -44,6 +50,17 @@ impl InputMode for PullUp {
         (original & !(0b11 << offset)) | (0b01 << offset)
     }
 }
+/// Pull-up/pull-down resistors configuration
+#[derive(Copy, Clone)]
+#[repr(u8)]
+pub enum Pull {
+    /// No pull-up, pull-down
+    None = 0b00,
+    /// Pull-up
+    Up = 0b01,
+    /// Pull-down
+    Down = 0b10,
+}
 /// Input mode (type state)
 pub struct Input<MODE> {
     _mode: PhantomData<MODE>,
@@ -78,6 +95,146 @@ pub struct Output<MODE> {
     _mode: PhantomData<MODE>,
 }
 
+/// Analog mode (type state)
+///
+/// Used by ADC/DAC channels. Also the lowest consumption state for unused pins.
+pub struct Analog;
+
+/// Output speed
+///
+/// Refer to the device datasheet for the frequency specifications of each speed.
+#[derive(Copy, Clone)]
+#[repr(u8)]
+pub enum Speed {
+    /// Low speed
+    Low = 0b00,
+    /// Medium speed
+    Medium = 0b01,
+    /// High speed
+    High = 0b10,
+    /// Very high speed
+    VeryHigh = 0b11,
+}
+
+/// Output type
+#[derive(Copy, Clone)]
+pub enum OutputType {
+    /// Push pull
+    PushPull,
+    /// Open drain
+    OpenDrain,
+}
+
+/// Alternate function output configuration
+#[derive(Copy, Clone)]
+pub struct AltCfg {
+    /// Output speed
+    pub speed: Speed,
+    /// Output type
+    pub otype: OutputType,
+}
+
+/// GPIO port index Trait
+/// Implemented only for device's GPIO ports.
+///
+/// Note: MUST not be implemented by user.
+pub trait PortIndex {
+    /// Index of port, starting from 0 for GPIOA
+    const INDEX: u8;
+}
+
+/// GPIO port
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Port {
+    /// GPIOA
+    A,
+    /// GPIOB
+    B,
+    /// GPIOC
+    C,
+    /// GPIOD
+    D,
+    /// GPIOE
+    E,
+    /// GPIOF
+    F,
+    /// GPIOG
+    G,
+    /// GPIOH
+    H,
+}
+
+impl Port {
+    /// Returns port by its index, starting from 0 for GPIOA
+    fn from_index(idx: u8) -> Self {
+        match idx {
+            0 => Port::A,
+            1 => Port::B,
+            2 => Port::C,
+            3 => Port::D,
+            4 => Port::E,
+            5 => Port::F,
+            6 => Port::G,
+            7 => Port::H,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Locks configuration of port's pins selected by `mask` until next reset.
+    ///
+    /// Once locked, MODER, OTYPER, OSPEEDR, PUPDR and AFR bits of the pins are frozen by HW,
+    /// and can be unlocked only by reset.
+    ///
+    /// Lock key sequence can succeed only once per port, after that LCKR itself is frozen.
+    /// Hence all pins of the port, that need to be locked, should be selected in a single call.
+    ///
+    /// Returns `true` if port is locked and all pins of `mask` are locked, see Reference Ch. 8.4.8
+    pub fn lock(self, mask: u16) -> bool {
+        // Lock key sequence must not be interrupted by other writes to LCKR
+        cortex_m::interrupt::free(|_| unsafe {
+            let lckr = &(*self.ptr()).lckr;
+            // Keep pins, that are already selected for locking, in the sequence
+            let bits = (lckr.read().bits() & 0xFFFF) | mask as u32;
+
+            lckr.write(|w| w.bits(LCKK | bits));
+            lckr.write(|w| w.bits(bits));
+            lckr.write(|w| w.bits(LCKK | bits));
+            // First read completes the sequence, second one confirms it
+            let _ = lckr.read();
+            let bits = lckr.read().bits();
+
+            bits & LCKK != 0 && bits & mask as u32 == mask as u32
+        })
+    }
+
+    /// Returns pointer to register block of port.
+    ///
+    /// All ports share the same register layout.
+    fn ptr(self) -> *const stm32l4x6::gpioa::RegisterBlock {
+        match self {
+            Port::A => stm32l4x6::GPIOA::ptr() as *const _,
+            Port::B => stm32l4x6::GPIOB::ptr() as *const _,
+            Port::C => stm32l4x6::GPIOC::ptr() as *const _,
+            Port::D => stm32l4x6::GPIOD::ptr() as *const _,
+            Port::E => stm32l4x6::GPIOE::ptr() as *const _,
+            Port::F => stm32l4x6::GPIOF::ptr() as *const _,
+            Port::G => stm32l4x6::GPIOG::ptr() as *const _,
+            Port::H => stm32l4x6::GPIOH::ptr() as *const _,
+        }
+    }
+}
+
+/// Edge that triggers external interrupt
+#[derive(Copy, Clone)]
+pub enum Edge {
+    /// Rising edge
+    Rising,
+    /// Falling edge
+    Falling,
+    /// Both rising and falling edges
+    RisingFalling,
+}
+
 /// Alternate Function Trait
 /// Implemented only for corresponding structs.
 ///
@@ -167,10 +324,16 @@ impl AltFun for AF15 {
     const NUM: u32 = 15;
 }
 
+/// Lock key bit of LCKR register
+const LCKK: u32 = 1 << 16;
+
 macro_rules! impl_parts {
-    ($($GPIOX:ident, $gpiox:ident;)+) => {
+    ($($GPIOX:ident, $gpiox:ident, $idx:expr;)+) => {
         $(
             use stm32l4x6::$GPIOX;
+            impl PortIndex for $GPIOX {
+                const INDEX: u8 = $idx;
+            }
             impl AFRL<$GPIOX> {
                 pub(crate) fn afr(&mut self) -> &stm32l4x6::$gpiox::AFRL {
                     unsafe { &(*$GPIOX::ptr()).afrl }
@@ -197,18 +360,61 @@ macro_rules! impl_parts {
                     unsafe { &(*$GPIOX::ptr()).pupdr }
                 }
             }
+            impl IDR<$GPIOX> {
+                /// Reads input state of all port's pins at once.
+                ///
+                /// Bit N corresponds to pin N. Only bits of pins configured as input are meaningful.
+                pub fn read_input(&self) -> u16 {
+                    // NOTE(unsafe) atomic read with no side effects
+                    unsafe { (*$GPIOX::ptr()).idr.read().bits() as u16 }
+                }
+            }
+            impl BSRR<$GPIOX> {
+                /// Drives output state of multiple port's pins at once.
+                ///
+                /// Pins selected by `mask` are set according to corresponding bits of `bits`,
+                /// all in a single write to BSRR. Other pins are not affected.
+                pub fn write_output(&mut self, bits: u16, mask: u16) {
+                    self.set_reset(bits & mask, !bits & mask);
+                }
+
+                /// Sets pins selected by `set_mask` high and pins selected by `reset_mask` low.
+                ///
+                /// Performed as a single write to BSRR, so it cannot be interleaved with other pin changes.
+                /// If pin is present in both masks, it is set high.
+                pub fn set_reset(&mut self, set_mask: u16, reset_mask: u16) {
+                    // NOTE(unsafe) atomic write to a stateless register
+                    unsafe { (*$GPIOX::ptr()).bsrr.write(|w| w.bits(((reset_mask as u32) << 16) | set_mask as u32)) }
+                }
+            }
+            impl OSPEEDR<$GPIOX> {
+                pub(crate) fn ospeedr(&mut self) -> &stm32l4x6::$gpiox::OSPEEDR {
+                    unsafe { &(*$GPIOX::ptr()).ospeedr }
+                }
+            }
          )+
     }
 }
 
+// Pins marked as `debug` are used by debugger after reset, and are guarded by `DebugPin`
+macro_rules! pin_type {
+    ($PXi:ident) => { $PXi<Input<Floating>> };
+    ($PXi:ident, debug) => { DebugPin<$PXi<AF0>> };
+}
+
+macro_rules! pin_init {
+    ($PXi:ident) => { $PXi(PhantomData) };
+    ($PXi:ident, debug) => { DebugPin($PXi(PhantomData)) };
+}
+
 macro_rules! impl_gpio {
     ($name:ident, $GPIOX:ident, $gpioen:ident, $gpiorst:ident) => {
         impl_gpio!($name, $GPIOX, $gpioen, $gpiorst, AFRL: [], AFRH: []);
     };
-    ($name:ident, $GPIOX:ident, $gpioen:ident, $gpiorst:ident, AFRL: [$($PXiL:ident, $iL:expr;)*]) => {
-        impl_gpio!($name, $GPIOX, $gpioen, $gpiorst, AFRL: [$($PXiL, $iL;)*], AFRH: []);
+    ($name:ident, $GPIOX:ident, $gpioen:ident, $gpiorst:ident, AFRL: [$($PXiL:ident, $iL:expr $(, $dbgL:ident)?;)*]) => {
+        impl_gpio!($name, $GPIOX, $gpioen, $gpiorst, AFRL: [$($PXiL, $iL $(, $dbgL)?;)*], AFRH: []);
     };
-    ($name:ident, $GPIOX:ident, $gpioen:ident, $gpiorst:ident, AFRL: [$($PXiL:ident, $iL:expr;)*], AFRH: [$($PXiH:ident, $iH:expr;)*]) => {
+    ($name:ident, $GPIOX:ident, $gpioen:ident, $gpiorst:ident, AFRL: [$($PXiL:ident, $iL:expr $(, $dbgL:ident)?;)*], AFRH: [$($PXiH:ident, $iH:expr $(, $dbgH:ident)?;)*]) => {
         impl_pins!($GPIOX, AFRL: [$($PXiL, $iL;)*]);
         impl_pins!($GPIOX, AFRH: [$($PXiH, $iH;)*]);
 
@@ -225,13 +431,19 @@ macro_rules! impl_gpio {
             pub otyper: OTYPER<$GPIOX>,
             /// Opaque PUPDR register
             pub pupdr: PUPDR<$GPIOX>,
+            /// Opaque OSPEEDR register
+            pub ospeedr: OSPEEDR<$GPIOX>,
+            /// Opaque IDR register
+            pub idr: IDR<$GPIOX>,
+            /// Opaque BSRR register
+            pub bsrr: BSRR<$GPIOX>,
             $(
                 /// Pin
-                pub $PXiL: $PXiL<Input<Floating>>,
+                pub $PXiL: pin_type!($PXiL $(, $dbgL)?),
             )*
             $(
                 /// Pin
-                pub $PXiH: $PXiH<Input<Floating>>,
+                pub $PXiH: pin_type!($PXiH $(, $dbgH)?),
             )*
         }
 
@@ -248,11 +460,14 @@ macro_rules! impl_gpio {
                     moder: MODER(PhantomData),
                     otyper: OTYPER(PhantomData),
                     pupdr: PUPDR(PhantomData),
+                    ospeedr: OSPEEDR(PhantomData),
+                    idr: IDR(PhantomData),
+                    bsrr: BSRR(PhantomData),
                     $(
-                        $PXiL: $PXiL(PhantomData),
+                        $PXiL: pin_init!($PXiL $(, $dbgL)?),
                     )*
                     $(
-                        $PXiH: $PXiH(PhantomData),
+                        $PXiH: pin_init!($PXiH $(, $dbgH)?),
                     )*
                 }
             }
@@ -287,6 +502,16 @@ macro_rules! impl_pin {
                 $PXi(PhantomData)
             }
 
+            /// Configures the PIN to operate as Analog Pin.
+            ///
+            /// Pull-up/pull-down resistors are disabled to avoid leakage through them.
+            pub fn into_analog(self, moder: &mut MODER<$GPIOX>, pupdr: &mut PUPDR<$GPIOX>) -> $PXi<Analog> {
+                pupdr.pupdr().modify(|r, w| unsafe { w.bits(Floating::modify_pupdr_bits(r.bits(), Self::OFFSET)) });
+                moder.moder().modify(|r, w| unsafe { w.bits(r.bits() | (0b11 << Self::OFFSET)) });
+
+                $PXi(PhantomData)
+            }
+
             /// Configures the PIN to operate as Alternate Function.
             pub fn into_alt_fun<AF: AltFun>(self, moder: &mut MODER<$GPIOX>, afr: &mut $AFR<$GPIOX>) -> $PXi<AF> {
                 // AFRx pin fields are 4 bits wide, and each 8-pin bank has its own reg (L or H); e.g. pin 8's offset is _0_, within AFRH.
@@ -298,6 +523,125 @@ macro_rules! impl_pin {
 
                 $PXi(PhantomData)
             }
+
+            /// Configures the PIN to operate as Alternate Function with open drain output.
+            ///
+            /// Required by I2C and similar open drain buses.
+            pub fn into_alt_fun_open_drain<AF: AltFun>(self, moder: &mut MODER<$GPIOX>, otyper: &mut OTYPER<$GPIOX>, afr: &mut $AFR<$GPIOX>) -> $PXi<AF> {
+                // Output type is set first so that the PIN never drives line in push pull mode.
+                otyper.otyper().modify(|r, w| unsafe { w.bits(OpenDrain::modify_otyper_bits(r.bits(), $i)) });
+
+                self.into_alt_fun(moder, afr)
+            }
+
+            /// Configures the PIN to operate as Alternate Function with specified output configuration.
+            ///
+            /// Output type and speed are set before switching to Alternate Function,
+            /// so that peripheral never drives PIN with wrong configuration.
+            pub fn into_alt_fun_cfg<AF: AltFun>(mut self, moder: &mut MODER<$GPIOX>, otyper: &mut OTYPER<$GPIOX>, ospeedr: &mut OSPEEDR<$GPIOX>, afr: &mut $AFR<$GPIOX>, cfg: AltCfg) -> $PXi<AF> {
+                otyper.otyper().modify(|r, w| unsafe {
+                    w.bits(match cfg.otype {
+                        OutputType::PushPull => PushPull::modify_otyper_bits(r.bits(), $i),
+                        OutputType::OpenDrain => OpenDrain::modify_otyper_bits(r.bits(), $i),
+                    })
+                });
+                self.set_speed(ospeedr, cfg.speed);
+
+                self.into_alt_fun(moder, afr)
+            }
+
+            /// Erases PIN's number, port and mode from its type.
+            ///
+            /// PIN should be configured before that, as `AnyPin` cannot change its mode.
+            pub fn into_any(self) -> AnyPin {
+                AnyPin {
+                    port: Port::from_index(<$GPIOX as PortIndex>::INDEX),
+                    i: $i,
+                }
+            }
+
+            /// Sets output speed of the PIN.
+            ///
+            /// Has effect only when PIN is in Output or Alternate Function mode.
+            pub fn set_speed(&mut self, ospeedr: &mut OSPEEDR<$GPIOX>, speed: Speed) {
+                ospeedr
+                    .ospeedr()
+                    .modify(|r, w| unsafe { w.bits((r.bits() & !(0b11 << Self::OFFSET)) | ((speed as u32) << Self::OFFSET)) });
+            }
+
+            /// Sets pull-up/pull-down resistors of the PIN in place.
+            ///
+            /// Note: type state of the PIN is not changed, use `into_input` for that.
+            pub fn set_pull(&mut self, pupdr: &mut PUPDR<$GPIOX>, pull: Pull) {
+                pupdr
+                    .pupdr()
+                    .modify(|r, w| unsafe { w.bits((r.bits() & !(0b11 << Self::OFFSET)) | ((pull as u32) << Self::OFFSET)) });
+            }
+
+            /// Locks configuration of the PIN until next reset.
+            ///
+            /// Once locked, MODER, OTYPER, OSPEEDR, PUPDR and AFR bits of the PIN are frozen by HW,
+            /// and can be unlocked only by reset.
+            ///
+            /// Note: lock key sequence can succeed only once per port, after that LCKR itself is frozen.
+            /// Hence pins of the same port should be locked at once with `Port::lock`.
+            ///
+            /// Returns `true` if the PIN is locked, `false` if port has been already locked without it.
+            pub fn lock(&mut self) -> bool {
+                Port::from_index(<$GPIOX as PortIndex>::INDEX).lock(1 << $i)
+            }
+
+            /// Returns whether configuration of the PIN is locked.
+            pub fn is_locked(&self) -> bool {
+                // NOTE(unsafe) atomic read with no side effects
+                let bits = unsafe { (*$GPIOX::ptr()).lckr.read().bits() };
+                bits & LCKK != 0 && bits & (1 << $i) != 0
+            }
+        }
+
+        impl<MODE> $PXi<Input<MODE>> {
+            /// Routes the PIN to its EXTI line.
+            ///
+            /// Note that each EXTI line can be driven only by a single port at a time.
+            pub fn make_interrupt_source(&mut self, syscfg: &mut Syscfg) {
+                syscfg.set_exti_port($i, <$GPIOX as PortIndex>::INDEX);
+            }
+
+            /// Selects edge(s) of PIN signal that trigger interrupt.
+            pub fn trigger_on_edge(&mut self, exti: &mut EXTI, edge: Edge) {
+                let (rising, falling) = match edge {
+                    Edge::Rising => (true, false),
+                    Edge::Falling => (false, true),
+                    Edge::RisingFalling => (true, true),
+                };
+
+                exti.rtsr1.modify(|r, w| unsafe { w.bits((r.bits() & !(1 << $i)) | ((rising as u32) << $i)) });
+                exti.ftsr1.modify(|r, w| unsafe { w.bits((r.bits() & !(1 << $i)) | ((falling as u32) << $i)) });
+            }
+
+            /// Unmasks interrupt of PIN's EXTI line.
+            pub fn enable_interrupt(&mut self, exti: &mut EXTI) {
+                exti.imr1.modify(|r, w| unsafe { w.bits(r.bits() | (1 << $i)) });
+            }
+
+            /// Masks interrupt of PIN's EXTI line.
+            pub fn disable_interrupt(&mut self, exti: &mut EXTI) {
+                exti.imr1.modify(|r, w| unsafe { w.bits(r.bits() & !(1 << $i)) });
+            }
+
+            /// Clears pending bit of PIN's EXTI line.
+            ///
+            /// Must be called from interrupt handler, otherwise it is going to be continuously called.
+            pub fn clear_interrupt_pending_bit(&mut self) {
+                // NOTE(unsafe) atomic write to a stateless register, cleared by writing 1
+                unsafe { (*EXTI::ptr()).pr1.write(|w| w.bits(1 << $i)) }
+            }
+
+            /// Returns whether PIN's EXTI line has pending interrupt.
+            pub fn check_interrupt(&self) -> bool {
+                // NOTE(unsafe) atomic read with no side effects
+                unsafe { (*EXTI::ptr()).pr1.read().bits() & (1 << $i) != 0 }
+            }
         }
 
         impl<MODE> InputPin for $PXi<Input<MODE>> {
@@ -313,6 +657,50 @@ macro_rules! impl_pin {
             }
         }
 
+        impl<MODE> $PXi<Output<MODE>> {
+            /// Reconfigures output PIN to operate as Input Pin according to Mode.
+            ///
+            /// Unlike `into_input`, it also restores OTYPER to its default push pull state.
+            /// MODER is switched first, so that PIN stops driving line before the rest of
+            /// configuration is changed.
+            pub fn reset_into_input<Mode: InputMode>(self, moder: &mut MODER<$GPIOX>, otyper: &mut OTYPER<$GPIOX>, pupdr: &mut PUPDR<$GPIOX>) -> $PXi<Input<Mode>> {
+                moder.moder().modify(|r, w| unsafe { w.bits(r.bits() & !(0b11 << Self::OFFSET)) });
+                otyper.otyper().modify(|r, w| unsafe { w.bits(PushPull::modify_otyper_bits(r.bits(), $i)) });
+                pupdr.pupdr().modify(|r, w| unsafe { w.bits(Mode::modify_pupdr_bits(r.bits(), Self::OFFSET)) });
+
+                $PXi(PhantomData)
+            }
+
+            #[inline]
+            /// Reconfigures output PIN into floating input.
+            pub fn into_floating_input(self, moder: &mut MODER<$GPIOX>, otyper: &mut OTYPER<$GPIOX>, pupdr: &mut PUPDR<$GPIOX>) -> $PXi<Input<Floating>> {
+                self.reset_into_input(moder, otyper, pupdr)
+            }
+
+            #[inline]
+            /// Reconfigures output PIN into pulled up input.
+            pub fn into_pull_up_input(self, moder: &mut MODER<$GPIOX>, otyper: &mut OTYPER<$GPIOX>, pupdr: &mut PUPDR<$GPIOX>) -> $PXi<Input<PullUp>> {
+                self.reset_into_input(moder, otyper, pupdr)
+            }
+
+            #[inline]
+            /// Reconfigures output PIN into pulled down input.
+            pub fn into_pull_down_input(self, moder: &mut MODER<$GPIOX>, otyper: &mut OTYPER<$GPIOX>, pupdr: &mut PUPDR<$GPIOX>) -> $PXi<Input<PullDown>> {
+                self.reset_into_input(moder, otyper, pupdr)
+            }
+
+            /// Erases PIN's number and port from its type.
+            ///
+            /// Allows to store pins of different ports together, at the cost of runtime dispatch.
+            pub fn downgrade(self) -> ErasedPin<Output<MODE>> {
+                ErasedPin {
+                    regs: $GPIOX::ptr() as *const _,
+                    i: $i,
+                    _mode: PhantomData,
+                }
+            }
+        }
+
         impl<MODE> OutputPin for $PXi<Output<MODE>> {
             /// Sets high bit.
             fn set_high(&mut self) {
@@ -339,6 +727,18 @@ macro_rules! impl_pin {
                 unsafe { (*$GPIOX::ptr()).odr.read().bits() & (1 << $i) == 0 }
             }
         }
+
+        impl<MODE> ToggleableOutputPin for $PXi<Output<MODE>> {
+            /// Toggles output state.
+            fn toggle(&mut self) {
+                let bit = match self.is_set_high() {
+                    true => 1 << (16 + $i),
+                    false => 1 << $i,
+                };
+                // NOTE(unsafe) atomic write to a stateless register
+                unsafe { (*$GPIOX::ptr()).bsrr.write(|w| w.bits(bit)) }
+            }
+        }
     };
 }
 
@@ -350,6 +750,137 @@ macro_rules! impl_pins {
     }
 }
 
+/// Pin used by debugger (SWD/SWO)
+///
+/// Reconfiguring such pin cuts off debug probe until device is reset, hence it must be
+/// explicitly released from debugger before it can be used.
+pub struct DebugPin<PIN>(PIN);
+impl<PIN> DebugPin<PIN> {
+    /// Releases PIN from debugger, making it possible to reconfigure it.
+    ///
+    /// Once PIN is reconfigured, debug probe can connect only under reset
+    /// (or with BOOT0 pulled high).
+    pub fn release_debug(self) -> PIN {
+        self.0
+    }
+}
+
+/// Type erased Pin
+///
+/// Keeps address of port's register block and index of pin at runtime.
+pub struct ErasedPin<MODE> {
+    regs: *const stm32l4x6::gpioa::RegisterBlock,
+    i: u8,
+    _mode: PhantomData<MODE>,
+}
+
+// NOTE(unsafe) pin accesses only atomic registers of its port
+unsafe impl<MODE> Send for ErasedPin<MODE> {}
+
+impl<MODE> OutputPin for ErasedPin<Output<MODE>> {
+    /// Sets high bit.
+    fn set_high(&mut self) {
+        // NOTE(unsafe) atomic write to a stateless register
+        unsafe { (*self.regs).bsrr.write(|w| w.bits(1 << self.i)) }
+    }
+
+    /// Sets low bit.
+    fn set_low(&mut self) {
+        // NOTE(unsafe) atomic write to a stateless register
+        unsafe { (*self.regs).bsrr.write(|w| w.bits(1 << (16 + self.i))) }
+    }
+}
+
+impl<MODE> StatefulOutputPin for ErasedPin<Output<MODE>> {
+    /// Returns whether high bit is set.
+    fn is_set_high(&self) -> bool {
+        !self.is_set_low()
+    }
+
+    /// Returns whether low bit is set.
+    fn is_set_low(&self) -> bool {
+        // NOTE(unsafe) atomic read with no side effects
+        unsafe { (*self.regs).odr.read().bits() & (1 << self.i) == 0 }
+    }
+}
+
+impl<MODE> ToggleableOutputPin for ErasedPin<Output<MODE>> {
+    /// Toggles output state.
+    fn toggle(&mut self) {
+        let bit = match self.is_set_high() {
+            true => 1 << (16 + self.i),
+            false => 1 << self.i,
+        };
+        // NOTE(unsafe) atomic write to a stateless register
+        unsafe { (*self.regs).bsrr.write(|w| w.bits(bit)) }
+    }
+}
+
+/// Pin of any port in any mode
+///
+/// Unlike `ErasedPin`, mode is not tracked, so it can be used both as input and output.
+/// Reading input works in any mode, while output is driven only in output mode.
+pub struct AnyPin {
+    port: Port,
+    i: u8,
+}
+
+impl AnyPin {
+    #[inline]
+    /// Returns port of the pin.
+    pub fn port(&self) -> Port {
+        self.port
+    }
+
+    #[inline]
+    /// Returns index of the pin within its port.
+    pub fn index(&self) -> u8 {
+        self.i
+    }
+}
+
+impl InputPin for AnyPin {
+    /// Returns whether bit is reading low.
+    fn is_low(&self) -> bool {
+        // NOTE(unsafe) atomic read with no side effects
+        unsafe { (*self.port.ptr()).idr.read().bits() & (1 << self.i) == 0 }
+    }
+
+    /// Returns whether bit is reading high.
+    fn is_high(&self) -> bool {
+        !self.is_low()
+    }
+}
+
+impl OutputPin for AnyPin {
+    /// Sets high bit.
+    fn set_high(&mut self) {
+        // NOTE(unsafe) atomic write to a stateless register
+        unsafe { (*self.port.ptr()).bsrr.write(|w| w.bits(1 << self.i)) }
+    }
+
+    /// Sets low bit.
+    fn set_low(&mut self) {
+        // NOTE(unsafe) atomic write to a stateless register
+        unsafe { (*self.port.ptr()).bsrr.write(|w| w.bits(1 << (16 + self.i))) }
+    }
+}
+
+impl StatefulOutputPin for AnyPin {
+    /// Returns whether high bit is set.
+    fn is_set_high(&self) -> bool {
+        !self.is_set_low()
+    }
+
+    /// Returns whether low bit is set.
+    fn is_set_low(&self) -> bool {
+        // NOTE(unsafe) atomic read with no side effects
+        unsafe { (*self.port.ptr()).odr.read().bits() & (1 << self.i) == 0 }
+    }
+}
+
+impl toggleable::Default for AnyPin {}
+
 /// Generic LED
 pub struct Led<PIN>(PIN);
 impl<PIN: OutputPin + StatefulOutputPin> Led<PIN> {
@@ -435,11 +966,17 @@ pub struct MODER<GPIO>(PhantomData<GPIO>);
 pub struct OTYPER<GPIO>(PhantomData<GPIO>);
 /// Opaque PUPDR register
 pub struct PUPDR<GPIO>(PhantomData<GPIO>);
+/// Opaque OSPEEDR register
+pub struct OSPEEDR<GPIO>(PhantomData<GPIO>);
+/// Opaque IDR register
+pub struct IDR<GPIO>(PhantomData<GPIO>);
+/// Opaque BSRR register
+pub struct BSRR<GPIO>(PhantomData<GPIO>);
 
 impl_parts!(
-    GPIOA, gpioa;
-    GPIOB, gpiob;
-    GPIOC, gpioc;
+    GPIOA, gpioa, 0;
+    GPIOB, gpiob, 1;
+    GPIOC, gpioc, 2;
     );
 
 // Each I/O pin (except PH3 for STM32L496xx/4A6xx devices) has a multiplexer with up to
@@ -448,12 +985,14 @@ impl_parts!(
 //
 // The GPIO ports (and pins) enumerated here are exposed on all package variants of the STM32L4x6.
 // Larger chips have more pins, and so have additional definitions in their respective modules.
+//
+// PA13 (SWDIO), PA14 (SWCLK) and PB3 (SWO) are used by debugger after reset.
 impl_gpio!(A, GPIOA, gpioaen, gpioarst,
            AFRL: [PA0, 0; PA1, 1; PA2, 2; PA3, 3; PA4, 4; PA5, 5; PA6, 6; PA7, 7;],
-           AFRH: [PA8, 8; PA9, 9; PA10, 10; PA11, 11; PA12, 12; PA13, 13; PA14, 14; PA15, 15; ]
+           AFRH: [PA8, 8; PA9, 9; PA10, 10; PA11, 11; PA12, 12; PA13, 13, debug; PA14, 14, debug; PA15, 15; ]
           );
 impl_gpio!(B, GPIOB, gpioben, gpiobrst,
-           AFRL: [PB0, 0; PB1, 1; PB2, 2; PB3, 3; PB4, 4; PB5, 5; PB6, 6; PB7, 7;],
+           AFRL: [PB0, 0; PB1, 1; PB2, 2; PB3, 3, debug; PB4, 4; PB5, 5; PB6, 6; PB7, 7;],
            AFRH: [PB8, 8; PB9, 9; PB10, 10; PB11, 11; PB12, 12; PB13, 13; PB14, 14; PB15, 15; ]
           );
 impl_gpio!(C, GPIOC, gpiocen, gpiocrst,
@@ -461,6 +1000,24 @@ impl_gpio!(C, GPIOC, gpiocen, gpiocrst,
            AFRH: [PC8, 8; PC9, 9; PC10, 10; PC11, 11; PC12, 12; PC13, 13; PC14, 14; PC15, 15; ]
           );
 
+// Ports D and E are exposed by packages with 100 pins and more, regardless of specific chip.
+// Enable "GPIO_DE" feature to use them.
+#[cfg(feature = "GPIO_DE")]
+impl_parts!(
+    GPIOD, gpioc, 3;
+    GPIOE, gpioc, 4;
+    );
+#[cfg(feature = "GPIO_DE")]
+impl_gpio!(D, GPIOD, gpioden, gpiodrst,
+           AFRL: [PD0, 0; PD1, 1; PD2, 2; PD3, 3; PD4, 4; PD5, 5; PD6, 6; PD7, 7;],
+           AFRH: [PD8, 8; PD9, 9; PD10, 10; PD11, 11; PD12, 12; PD13, 13; PD14, 14; PD15, 15; ]
+          );
+#[cfg(feature = "GPIO_DE")]
+impl_gpio!(E, GPIOE, gpioeen, gpioerst,
+           AFRL: [PE0, 0; PE1, 1; PE2, 2; PE3, 3; PE4, 4; PE5, 5; PE6, 6; PE7, 7;],
+           AFRH: [PE8, 8; PE9, 9; PE10, 10; PE11, 11; PE12, 12; PE13, 13; PE14, 14; PE15, 15; ]
+          );
+
 #[cfg(feature = "STM32L476VG")]
 pub mod stm32l476vg;
 
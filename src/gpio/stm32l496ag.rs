@@ -11,11 +11,9 @@ use stm32l4x6;
 use super::*;
 
 impl_parts!(
-    GPIOD, gpioc;
-    GPIOE, gpioc;
-    GPIOF, gpioc;
-    GPIOG, gpioc;
-    GPIOH, gpioc;
+    GPIOF, gpioc, 5;
+    GPIOG, gpioc, 6;
+    GPIOH, gpioc, 7;
     );
 
 /// Description of GPIOs and PINs
@@ -23,14 +21,6 @@ pub mod gpio {
     pub use super::super::*;
     use super::*;
 
-    impl_gpio!(D, GPIOD, gpioden, gpiodrst,
-               AFRL: [PD0, 0; PD1, 1; PD2, 2; PD3, 3; PD4, 4; PD5, 5; PD6, 6; PD7, 7;],
-               AFRH: [PD8, 8; PD9, 9; PD10, 10; PD11, 11; PD12, 12; PD13, 13; PD14, 14; PD15, 15; ]
-    );
-    impl_gpio!(E, GPIOE, gpioeen, gpioerst,
-               AFRL: [PE0, 0; PE1, 1; PE2, 2; PE3, 3; PE4, 4; PE5, 5; PE6, 6; PE7, 7;],
-               AFRH: [PE8, 8; PE9, 9; PE10, 10; PE11, 11; PE12, 12; PE13, 13; PE14, 14; PE15, 15; ]
-    );
     impl_gpio!(F, GPIOF, gpiofen, gpiofrst,
                AFRL: [PF0, 0; PF1, 1; PF2, 2; PF3, 3; PF4, 4; PF5, 5; PF6, 6; PF7, 7;],
                AFRH: [PF8, 8; PF9, 9; PF10, 10; PF11, 11; PF12, 12; PF13, 13; PF14, 14; PF15, 15; ]
@@ -4,7 +4,7 @@
 
 use crate::gpio;
 use crate::power::Power;
-use crate::rcc::clocking::RtcClkSource;
+use crate::rcc::clocking::{HighSpeedExternalOSC, RtcClkSource};
 use crate::rcc::{APB1, AHB, BDCR};
 
 use core::mem;
@@ -125,7 +125,7 @@ impl LCD {
         //SEG6
         gpio.PB1.into_alt_fun::<gpio::AF11>(&mut gpio.moder, &mut gpio.afrl);
         //SEG7
-        gpio.PB3.into_alt_fun::<gpio::AF11>(&mut gpio.moder, &mut gpio.afrl);
+        gpio.PB3.release_debug().into_alt_fun::<gpio::AF11>(&mut gpio.moder, &mut gpio.afrl);
         //SEG8
         gpio.PB4.into_alt_fun::<gpio::AF11>(&mut gpio.moder, &mut gpio.afrl);
         //SEG9
@@ -196,9 +196,10 @@ impl LCD {
 
     /// Performs validation of settings.
     ///
-    /// HSE clock is not supported yet...
-    pub fn validate(lcd: &mut stm32l4x6::LCD, bdcr: &mut BDCR, configuration: &config::Config) -> ValidationResult {
-        let clock_frequency: u32 = match bdcr.rtc_clock().freq(None) {
+    /// When RTC/LCD clock is HSE divided by 32, configured HSE must be provided as `hse`,
+    /// otherwise `ClockNotSet` is returned.
+    pub fn validate(lcd: &mut stm32l4x6::LCD, bdcr: &mut BDCR, hse: Option<HighSpeedExternalOSC>, configuration: &config::Config) -> ValidationResult {
+        let clock_frequency: u32 = match bdcr.rtc_clock().freq(hse) {
             Some(f) => f,
             None => return ValidationResult::ClockNotSet,
         };
@@ -32,4 +32,8 @@ pub mod time;
 pub mod timer;
 pub mod spi;
 pub mod crc;
+#[cfg(feature = "STM32L496AG")]
+pub mod crs;
 pub mod serial;
+pub mod signature;
+pub mod syscfg;
@@ -1,8 +1,15 @@
 //! Power control
 
-use stm32l4x6::{pwr, PWR};
+use core::ptr;
+
+use cortex_m::asm;
+use cortex_m::peripheral::SCB;
+use stm32l4x6::{pwr, EXTI, PWR};
 
 use crate::common::Constrain;
+use crate::gpio::Edge;
+use crate::rcc::clocking::ClockError;
+use crate::rcc::Clocks;
 
 impl Constrain<Power> for PWR {
     fn constrain(self) -> Power {
@@ -10,6 +17,113 @@ impl Constrain<Power> for PWR {
     }
 }
 
+//LPMS of CR1, low-power mode entered on deep sleep
+const LPMS_STOP0: u8 = 0b000;
+const LPMS_STOP1: u8 = 0b001;
+const LPMS_STOP2: u8 = 0b010;
+const LPMS_STANDBY: u8 = 0b011;
+const LPMS_SHUTDOWN: u8 = 0b100;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u8)]
+/// Voltage scaling range of main regulator (see 5.1.8)
+pub enum VoltageRange {
+    /// High performance range, allows SYSCLK up to 80 MHz
+    Range1 = 0b01,
+    /// Low-power range, allows SYSCLK up to 26 MHz
+    Range2 = 0b10,
+}
+
+impl VoltageRange {
+    /// Returns currently selected range.
+    pub fn current() -> Self {
+        match unsafe { (*PWR::ptr()).cr1.read().vos().bits() } {
+            0b01 => VoltageRange::Range1,
+            _ => VoltageRange::Range2,
+        }
+    }
+
+    /// Selects the range and waits for regulator to reach it.
+    pub(crate) fn select(self) {
+        let pwr = unsafe { &(*PWR::ptr()) };
+        pwr.cr1.modify(|_, w| unsafe { w.vos().bits(self as u8) });
+        while pwr.sr2.read().vosf().bit_is_set() {}
+    }
+}
+
+//SRAM2 base address, it is also aliased right after SRAM1 (see 2.4.2)
+const SRAM2_BASE: usize = 0x1000_0000;
+/// Size of SRAM2 in bytes
+#[cfg(feature = "STM32L496AG")]
+pub const SRAM2_SIZE: usize = 64 * 1024;
+/// Size of SRAM2 in bytes
+#[cfg(not(feature = "STM32L496AG"))]
+pub const SRAM2_SIZE: usize = 32 * 1024;
+
+/// Maximum value for System clock in low-power run mode.
+pub const LOW_POWER_RUN_MAX: u32 = 2_000_000;
+
+//PVD output is connected to EXTI line 16
+const EXTI_PVD: u32 = 1 << 16;
+
+#[derive(Copy, Clone)]
+#[repr(u8)]
+/// Threshold of programmable voltage detector, at which VDD falls (see 5.2.3)
+///
+/// Voltages are typical, rising threshold is about 100 mV higher.
+pub enum PvdLevel {
+    /// 2.0 V
+    V2_0 = 0b000,
+    /// 2.2 V
+    V2_2 = 0b001,
+    /// 2.4 V
+    V2_4 = 0b010,
+    /// 2.5 V
+    V2_5 = 0b011,
+    /// 2.6 V
+    V2_6 = 0b100,
+    /// 2.8 V
+    V2_8 = 0b101,
+    /// 2.9 V
+    V2_9 = 0b110,
+    /// External input PVD_IN on PB7, compared with internal reference
+    External = 0b111,
+}
+
+#[derive(Copy, Clone)]
+/// Resistor through which battery on VBAT is charged
+pub enum VbatResistor {
+    /// 5 kOhm
+    R5k,
+    /// 1.5 kOhm
+    R1_5k,
+}
+
+#[derive(Copy, Clone)]
+#[repr(u8)]
+/// Wakeup pins, which can wake up MCU from Standby and Shutdown modes
+///
+/// Pins are used regardless of their GPIO configuration.
+pub enum WakeupPin {
+    /// WKUP1 on PA0
+    WKUP1 = 0,
+    /// WKUP2 on PC13
+    WKUP2 = 1,
+    /// WKUP3 on PE6
+    WKUP3 = 2,
+    /// WKUP4 on PA2
+    WKUP4 = 3,
+    /// WKUP5 on PC5
+    WKUP5 = 4,
+}
+
+impl WakeupPin {
+    #[inline]
+    fn mask(self) -> u32 {
+        1 << (self as u8)
+    }
+}
+
 /// Constrained Power control module
 pub struct Power(());
 impl Power {
@@ -41,6 +155,298 @@ impl Power {
         unsafe { &(*PWR::ptr()).scr }
     }
 
+    /// Selects voltage scaling range and waits for regulator to reach it.
+    ///
+    /// Range 1 is required for SYSCLK above `rcc::SYS_CLOCK_MAX_RANGE2`,
+    /// which `CFGR::freeze` and `CR::switch_sysclk` select on their own.
+    /// Before selecting Range 2, SYSCLK must be lowered to at most `rcc::SYS_CLOCK_MAX_RANGE2`
+    /// and flash latency increased as required by Range 2, see `ACR::set_latency_for`.
+    ///
+    /// See Reference manual Ch. 5.1.8
+    pub fn set_voltage_range(&mut self, range: VoltageRange) {
+        range.select();
+    }
+
+    /// Enables programmable voltage detector, which monitors VDD against `level`.
+    ///
+    /// See Reference manual Ch. 5.2.3
+    pub fn enable_pvd(&mut self, level: PvdLevel) {
+        self.cr2().modify(|_, w| unsafe { w.pls().bits(level as u8).pvde().set_bit() });
+    }
+
+    /// Disables programmable voltage detector.
+    pub fn disable_pvd(&mut self) {
+        self.cr2().modify(|_, w| w.pvde().clear_bit());
+    }
+
+    /// Returns whether VDD is below threshold of programmable voltage detector.
+    pub fn is_below_pvd(&mut self) -> bool {
+        self.sr2().read().pvdo().bit_is_set()
+    }
+
+    /// Enables interrupt of programmable voltage detector on EXTI line 16.
+    ///
+    /// `Edge::Rising` triggers when VDD falls below threshold, `Edge::Falling` when it rises back.
+    pub fn enable_pvd_interrupt(&mut self, exti: &mut EXTI, edge: Edge) {
+        let (rising, falling) = match edge {
+            Edge::Rising => (EXTI_PVD, 0),
+            Edge::Falling => (0, EXTI_PVD),
+            Edge::RisingFalling => (EXTI_PVD, EXTI_PVD),
+        };
+
+        exti.rtsr1.modify(|r, w| unsafe { w.bits((r.bits() & !EXTI_PVD) | rising) });
+        exti.ftsr1.modify(|r, w| unsafe { w.bits((r.bits() & !EXTI_PVD) | falling) });
+        exti.imr1.modify(|r, w| unsafe { w.bits(r.bits() | EXTI_PVD) });
+    }
+
+    /// Disables interrupt of programmable voltage detector.
+    pub fn disable_pvd_interrupt(&mut self, exti: &mut EXTI) {
+        exti.imr1.modify(|r, w| unsafe { w.bits(r.bits() & !EXTI_PVD) });
+    }
+
+    /// Clears pending bit of programmable voltage detector's EXTI line.
+    ///
+    /// Must be called from interrupt handler, otherwise it is going to be continuously called.
+    pub fn clear_pvd_interrupt_pending_bit(&mut self) {
+        // NOTE(unsafe) atomic write to a stateless register, cleared by writing 1
+        unsafe { (*EXTI::ptr()).pr1.write(|w| w.bits(EXTI_PVD)) }
+    }
+
+    /// Sets whether content of SRAM2 is retained in Standby mode.
+    ///
+    /// SRAM2 is then powered by low-power regulator, increasing consumption in Standby.
+    /// Unlike backup registers of RTC, SRAM2 is not in backup domain, so `remove_bdp` is not needed.
+    /// SRAM2 is always retained in Stop modes and never in Shutdown mode.
+    /// For its parity check see `Syscfg::is_sram2_parity_enabled`.
+    ///
+    /// See Reference manual Ch. 5.3.9
+    pub fn set_sram2_retention(&mut self, is_retained: bool) {
+        self.cr3().modify(|_, w| w.rrs().bit(is_retained));
+    }
+
+    /// Reads content of SRAM2 at `offset` into `buf`.
+    ///
+    /// SRAM2 must not be used by linker script (`memory.x`), when it is accessed this way.
+    ///
+    /// # Panics
+    ///
+    /// If the region exceeds `SRAM2_SIZE`.
+    pub fn read_sram2(&self, offset: usize, buf: &mut [u8]) {
+        assert!(Self::is_sram2_region(offset, buf.len()), "Region exceeds SRAM2");
+        for (idx, byte) in buf.iter_mut().enumerate() {
+            *byte = unsafe { ptr::read_volatile((SRAM2_BASE + offset + idx) as *const u8) };
+        }
+    }
+
+    /// Writes `data` into SRAM2 at `offset`, see `read_sram2`.
+    ///
+    /// # Panics
+    ///
+    /// If the region exceeds `SRAM2_SIZE`.
+    pub fn write_sram2(&mut self, offset: usize, data: &[u8]) {
+        assert!(Self::is_sram2_region(offset, data.len()), "Region exceeds SRAM2");
+        for (idx, byte) in data.iter().enumerate() {
+            unsafe { ptr::write_volatile((SRAM2_BASE + offset + idx) as *mut u8, *byte) };
+        }
+    }
+
+    /// Returns whether region of `len` bytes at `offset` lies within SRAM2.
+    fn is_sram2_region(offset: usize, len: usize) -> bool {
+        // Offset may be huge enough to wrap around
+        match offset.checked_add(len) {
+            Some(end) => end <= SRAM2_SIZE,
+            None => false,
+        }
+    }
+
+    /// Enables charging of battery on VBAT from VDD through `resistor`.
+    ///
+    /// Only rechargeable batteries or supercapacitors may be charged, as charging primary cells
+    /// (e.g. lithium coin cells) can damage them. Charging is stopped automatically in VBAT mode.
+    ///
+    /// See Reference manual Ch. 5.1.6
+    pub fn enable_vbat_charging(&mut self, resistor: VbatResistor) {
+        let is_1_5k = match resistor {
+            VbatResistor::R5k => false,
+            VbatResistor::R1_5k => true,
+        };
+        self.cr4().modify(|_, w| w.vbrs().bit(is_1_5k).vbe().set_bit());
+    }
+
+    /// Disables charging of battery on VBAT.
+    pub fn disable_vbat_charging(&mut self) {
+        self.cr4().modify(|_, w| w.vbe().clear_bit());
+    }
+
+    /// Switches regulator into low-power mode, while CPU keeps running.
+    ///
+    /// Returns `ClockError::InvalidFrequency` if SYSCLK exceeds `LOW_POWER_RUN_MAX`, which
+    /// must not be increased until `exit_low_power_run`. SYSCLK can be lowered beforehand
+    /// e.g. by `rcc::CR::set_msi_range`.
+    ///
+    /// See Reference manual Ch. 5.3.2
+    pub fn enter_low_power_run(&mut self, clocks: &Clocks) -> Result<(), ClockError> {
+        if clocks.sysclk().0 > LOW_POWER_RUN_MAX {
+            return Err(ClockError::InvalidFrequency);
+        }
+
+        self.cr1().modify(|_, w| w.lpr().set_bit());
+        while self.sr2().read().reglpf().bit_is_clear() {}
+        Ok(())
+    }
+
+    /// Switches regulator back into main mode, after which SYSCLK can be increased.
+    pub fn exit_low_power_run(&mut self) {
+        self.cr1().modify(|_, w| w.lpr().clear_bit());
+        while self.sr2().read().reglpf().bit_is_set() {}
+    }
+
+    /// Enters Sleep mode, until interrupt wakes up CPU.
+    ///
+    /// Only CPU clock is stopped, while peripherals keep running along with their clocks and interrupts.
+    /// Any enabled interrupt wakes up CPU, which resumes execution right after this call.
+    ///
+    /// See Reference manual Ch. 5.3.5
+    pub fn sleep(&mut self, scb: &mut SCB) {
+        scb.clear_sleepdeep();
+        asm::wfi();
+    }
+
+    /// Enters Stop 0 mode, until EXTI line wakes up MCU.
+    ///
+    /// All clocks in core domain are stopped, except LSE and LSI, while main regulator is kept on,
+    /// allowing the fastest wakeup. SRAM and registers are retained.
+    /// Peripherals which can run on LSE, LSI or HSI16 (see `rcc::CR::enable_hsi16`) keep running:
+    /// RTC, LCD, IWDG, LPTIM1/2, LPUART1, USART1-5, I2C1-3 and COMP.
+    ///
+    /// Wakeup is caused only by EXTI line enabled in interrupt or event mode (e.g. GPIO, RTC alarm,
+    /// or wakeup of peripheral), after which execution resumes right after this call.
+    /// SYSCLK is then driven by MSI or HSI16, as selected by STOPWUCK of RCC CFGR, so clocks
+    /// need to be configured again, e.g. by `rcc::CR::switch_sysclk`.
+    ///
+    /// See Reference manual Ch. 5.3.6
+    pub fn stop0(&mut self, scb: &mut SCB) {
+        self.enter_low_power_mode(scb, LPMS_STOP0);
+    }
+
+    /// Enters Stop 1 mode, same as `stop0`, but with low-power regulator.
+    ///
+    /// It consumes less, but takes longer to wake up.
+    ///
+    /// See Reference manual Ch. 5.3.7
+    pub fn stop1(&mut self, scb: &mut SCB) {
+        self.enter_low_power_mode(scb, LPMS_STOP1);
+    }
+
+    /// Enters Stop 2 mode, same as `stop1`, but with most of the peripherals powered off.
+    ///
+    /// Only RTC, LCD, IWDG, LPTIM1, LPUART1, I2C3 and COMP keep running, while
+    /// registers of other peripherals are still retained.
+    ///
+    /// See Reference manual Ch. 5.3.8
+    pub fn stop2(&mut self, scb: &mut SCB) {
+        self.enter_low_power_mode(scb, LPMS_STOP2);
+    }
+
+    /// Enables wakeup from Standby and Shutdown modes on `edge` of `pin`.
+    ///
+    /// # Panics
+    ///
+    /// When `edge` is `Edge::RisingFalling`, as wakeup is possible only on one of them.
+    ///
+    /// See Reference manual Ch. 5.4.3 and 5.4.4
+    pub fn enable_wakeup_pin(&mut self, pin: WakeupPin, edge: Edge) {
+        let mask = pin.mask();
+        // Polarity is changed while pin is disabled, as it may cause a false wakeup event
+        self.cr3().modify(|r, w| unsafe { w.bits(r.bits() & !mask) });
+        match edge {
+            Edge::Rising => self.cr4().modify(|r, w| unsafe { w.bits(r.bits() & !mask) }),
+            Edge::Falling => self.cr4().modify(|r, w| unsafe { w.bits(r.bits() | mask) }),
+            Edge::RisingFalling => panic!("Wakeup pin cannot be triggered on both edges"),
+        }
+        self.cr3().modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+    }
+
+    /// Disables wakeup on `pin`.
+    pub fn disable_wakeup_pin(&mut self, pin: WakeupPin) {
+        self.cr3().modify(|r, w| unsafe { w.bits(r.bits() & !pin.mask()) });
+    }
+
+    /// Clears flag of wakeup event on `pin`.
+    ///
+    /// Wakeup event is detected also in Run mode, and while it is set, MCU cannot enter Standby or Shutdown.
+    pub fn clear_wakeup_flag(&mut self, pin: WakeupPin) {
+        // NOTE(unsafe) Writing 0 to SCR has no effect
+        self.scr().write(|w| unsafe { w.bits(pin.mask()) });
+    }
+
+    /// Returns whether wakeup event occurred on `pin`, i.e. it caused wakeup from Standby or Shutdown.
+    pub fn wakeup_flag(&mut self, pin: WakeupPin) -> bool {
+        self.sr1().read().bits() & pin.mask() != 0
+    }
+
+    /// Clears flags of wakeup events on all pins.
+    pub fn clear_wakeup_flags(&mut self) {
+        self.scr().write(|w| w.wuf1().set_bit().wuf2().set_bit().wuf3().set_bit().wuf4().set_bit().wuf5().set_bit());
+    }
+
+    /// Returns whether MCU has been reset by wakeup from Standby mode.
+    ///
+    /// The flag is kept until cleared by `clear_standby_flag`.
+    pub fn woke_from_standby(&mut self) -> bool {
+        // SBF of SR1 is misnamed as CSBF by the device crate
+        self.sr1().read().csbf().bit_is_set()
+    }
+
+    /// Clears flag of wakeup from Standby mode.
+    pub fn clear_standby_flag(&mut self) {
+        self.scr().write(|w| w.sbf().set_bit());
+    }
+
+    /// Enters Standby mode, from which MCU wakes up by reset.
+    ///
+    /// Core domain is powered off, so content of SRAM1 and registers is lost, as well as
+    /// of SRAM2, unless retained by `set_sram2_retention`, and execution restarts from reset vector. Only backup domain (RTC and backup registers),
+    /// LSE, LSI and IWDG are kept running.
+    ///
+    /// Wakeup is caused by wakeup pins enabled by `enable_wakeup_pin`, RTC event, IWDG or NRST.
+    /// Wakeup flags are cleared before entering, otherwise MCU would wake up immediately.
+    ///
+    /// See Reference manual Ch. 5.3.9
+    pub fn standby(&mut self, scb: &mut SCB) -> ! {
+        self.enter_reset_mode(scb, LPMS_STANDBY)
+    }
+
+    /// Enters Shutdown mode, the lowest-power mode, from which MCU wakes up by reset.
+    ///
+    /// Unlike Standby, also regulator, brown-out reset and LSI are turned off, so neither SRAM2
+    /// can be retained, nor IWDG keeps running, and RTC can operate only on LSE.
+    /// Backup domain is retained.
+    ///
+    /// Wakeup is caused by wakeup pins enabled by `enable_wakeup_pin`, RTC event or NRST.
+    /// Wakeup flags are cleared before entering, otherwise MCU would wake up immediately.
+    ///
+    /// See Reference manual Ch. 5.3.10
+    pub fn shutdown(&mut self, scb: &mut SCB) -> ! {
+        self.enter_reset_mode(scb, LPMS_SHUTDOWN)
+    }
+
+    fn enter_reset_mode(&mut self, scb: &mut SCB, lpms: u8) -> ! {
+        self.clear_wakeup_flags();
+        self.cr1().modify(|_, w| unsafe { w.lpms().bits(lpms) });
+        scb.set_sleepdeep();
+        loop {
+            asm::wfi();
+        }
+    }
+
+    fn enter_low_power_mode(&mut self, scb: &mut SCB, lpms: u8) {
+        self.cr1().modify(|_, w| unsafe { w.lpms().bits(lpms) });
+        scb.set_sleepdeep();
+        asm::wfi();
+        scb.clear_sleepdeep();
+    }
+
     /// Removes write protection from Backup Domain Control register.
     pub fn remove_bdp(&mut self) {
         let cr1 = self.cr1();
@@ -16,7 +16,7 @@
 //! use stm32l4x6_hal::rcc::clocking;
 //!
 //! let mut rcc = RCC.constrain();
-//! let msi_clk = clocking::MediumSpeedInternalRC::new(8_000_000, false);
+//! let msi_clk = clocking::MediumSpeedInternalRC::new(8_000_000, false).unwrap();
 //! let sys_clk_src = clocking::SysClkSource::MSI(msi_clk);
 //! let cfgr = rcc.cfgr.sysclk(sys_clk_src);
 //! ```
@@ -24,8 +24,49 @@
 //! The PLL is a bit more complex because it _is_ a source (`PLLClkOutput`) and also _requires_
 //! a source (`PLLClkSource`), but you compose the types similarly.
 
+use stm32l4x6::PWR;
+
 use super::rcc;
 
+/// Number of iterations to wait for the LSE oscillator to become ready.
+///
+/// LSE may take up to few seconds to start, depending on the crystal and drive capability.
+pub const LSE_READY_RETRIES: u32 = 8_000_000;
+
+/// Allowed deviation of the 48 MHz domain, which is ±0.25% as required by USB
+pub const CLK48_TOLERANCE: u32 = 120_000;
+
+/// Errors of clock configuration
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ClockError {
+    /// Clock did not become ready in time, e.g. crystal is missing or broken
+    Timeout,
+    /// Clock does not have frequency required by its consumer
+    InvalidFrequency,
+}
+
+/// Waits until `is_ready` returns true, at most `retries` times or indefinitely if `None`.
+fn wait_ready<F: Fn() -> bool>(retries: Option<u32>, is_ready: F) -> Result<(), ClockError> {
+    match retries {
+        None => while !is_ready() {},
+        Some(mut retries) => while !is_ready() {
+            if retries == 0 {
+                return Err(ClockError::Timeout);
+            }
+            retries -= 1;
+        },
+    }
+    Ok(())
+}
+
+/// Checks that `freq` is suitable for the 48 MHz domain, i.e. within `CLK48_TOLERANCE`.
+pub fn check_clk48(freq: u32) -> Result<u32, ClockError> {
+    match freq {
+        47_880_000...48_120_000 => Ok(freq),
+        _ => Err(ClockError::InvalidFrequency),
+    }
+}
+
 /// Clocks (OSCs or RCs) that can be used as inputs to peripherals
 ///
 /// This trait isn't actually specified anywhere, and is used only by convention.
@@ -46,31 +87,72 @@ pub struct HighSpeedInternal16RC {
 impl HighSpeedInternal16RC {
     /// Applies the selection options to the configuration registers and turns the clock on
     pub fn configure(&self, rcc: &rcc::RegisterBlock) -> (u32, u8) {
+        self.try_configure(rcc, None).unwrap()
+    }
+
+    /// Same as `configure`, but waits for the clock at most `retries` times, or indefinitely if `None`.
+    pub fn try_configure(&self, rcc: &rcc::RegisterBlock, retries: Option<u32>) -> Result<(u32, u8), ClockError> {
         rcc.cr.modify(|_, w| w.hsion().set_bit().hsikeron().bit(self.always_on).hsiasfs().bit(self.auto_start));
-        while rcc.cr.read().hsirdy().bit_is_clear() {}
-        (16_000_000, 0b01)
+        wait_ready(retries, || rcc.cr.read().hsirdy().bit_is_set())?;
+        Ok((16_000_000, 0b01))
+    }
+}
+
+/// High-speed internal 48 MHz RC
+///
+/// Dedicated clock of the 48 MHz domain (USB FS, RNG, SDMMC), available only on STM32L49x/L4Ax.
+#[cfg(feature = "STM32L496AG")]
+#[derive(Clone, Copy)]
+pub struct HighSpeedInternal48RC;
+
+#[cfg(feature = "STM32L496AG")]
+impl HighSpeedInternal48RC {
+    //RCC_CRRCR is not described by the device crate (see 6.4.31)
+    const CRRCR_OFFSET: usize = 0x98;
+    const HSI48ON: u32 = 1;
+    const HSI48RDY: u32 = 1 << 1;
+
+    /// Turns on the HSI48 RC and waits for it to stabilize.
+    pub fn configure(&self, rcc: &rcc::RegisterBlock) -> u32 {
+        self.try_configure(rcc, None).unwrap()
+    }
+
+    /// Same as `configure`, but waits for the clock at most `retries` times, or indefinitely if `None`.
+    pub fn try_configure(&self, rcc: &rcc::RegisterBlock, retries: Option<u32>) -> Result<u32, ClockError> {
+        let crrcr = (rcc as *const rcc::RegisterBlock as usize + Self::CRRCR_OFFSET) as *mut u32;
+        // NOTE(unsafe) CRRCR is a valid register of RCC, only HSI48ON is writable
+        unsafe { core::ptr::write_volatile(crrcr, core::ptr::read_volatile(crrcr) | Self::HSI48ON) };
+        wait_ready(retries, || Self::is_ready(rcc))?;
+        Ok(self.freq())
+    }
+
+    /// Returns whether the HSI48 RC is running.
+    pub fn is_ready(rcc: &rcc::RegisterBlock) -> bool {
+        let crrcr = (rcc as *const rcc::RegisterBlock as usize + Self::CRRCR_OFFSET) as *const u32;
+        unsafe { core::ptr::read_volatile(crrcr) & Self::HSI48RDY != 0 }
+    }
+}
+
+#[cfg(feature = "STM32L496AG")]
+impl InputClock for HighSpeedInternal48RC {
+    fn freq(&self) -> u32 {
+        48_000_000
     }
 }
 
 /// Medium-speed internal 100 kHz - 48 MHz RC
 #[derive(Clone, Copy)]
 pub struct MediumSpeedInternalRC {
-    freq: u32,
+    range: u8,
     auto_cal: bool,
 }
 
 impl MediumSpeedInternalRC {
     /// Create a new MSI RC
     ///
-    /// `freq` must be a valid MSI RC frequency range (see 6.2.3)
-    /// TODO make freq a repr(C) enum
-    pub fn new(freq: u32, auto_cal: bool) -> Self {
-        MediumSpeedInternalRC { freq, auto_cal }
-    }
-
-    /// Convert the freq range to MSIRANGE bits (6.4.1). Panics if `freq` is invalid.
-    pub fn bits(&self) -> u8 {
-        match self.freq {
+    /// Returns `ClockError::InvalidFrequency` if `freq` is not a valid MSI RC frequency range (see 6.2.3)
+    pub fn new(freq: u32, auto_cal: bool) -> Result<Self, ClockError> {
+        let range = match freq {
             100_000 => 0b0000,
             200_000 => 0b0001,
             400_000 => 0b0010,
@@ -83,35 +165,119 @@ impl MediumSpeedInternalRC {
             24_000_000 => 0b1001,
             32_000_000 => 0b1010,
             48_000_000 => 0b1011,
-            _ => panic!("bad MSI speed value!"),
+            _ => return Err(ClockError::InvalidFrequency),
+        };
+        Ok(MediumSpeedInternalRC { range, auto_cal })
+    }
+
+    /// Create MSI RC from MSIRANGE bits, as they are read from the register.
+    pub(crate) fn from_range(range: u8, auto_cal: bool) -> Self {
+        MediumSpeedInternalRC { range, auto_cal }
+    }
+
+    /// Return MSIRANGE bits (6.4.1) of the freq range.
+    pub fn bits(&self) -> u8 {
+        self.range
+    }
+
+    /// Returns range bits, MSI currently runs at.
+    pub(crate) fn current_range(rcc: &rcc::RegisterBlock) -> u8 {
+        //MSIRGSEL is not readable through the device crate
+        const MSIRGSEL: u32 = 1 << 3;
+
+        // After Standby, MSI range is determined by MSISRANGE of CSR, until MSIRGSEL is set
+        let cr = rcc.cr.read();
+        match cr.bits() & MSIRGSEL {
+            0 => rcc.csr.read().msisrange().bits(),
+            _ => cr.msirange().bits(),
+        }
+    }
+
+    /// Returns whether MSI drives running PLL.
+    pub(crate) fn is_pll_input(rcc: &rcc::RegisterBlock) -> bool {
+        rcc.cr.read().pllrdy().bit_is_set() && rcc.pllcfgr.read().pllsrc().bits() == 0b01
+    }
+
+    /// Returns whether MSI drives SYSCLK or running PLL, so its range must not change.
+    pub(crate) fn is_in_use(rcc: &rcc::RegisterBlock) -> bool {
+        Self::is_pll_input(rcc) || rcc.cfgr.read().sws().bits() == 0b00
+    }
+
+    /// Convert MSIRANGE bits (6.4.1) to the frequency range. Returns `None` if `bits` are invalid.
+    pub fn range_freq(bits: u8) -> Option<u32> {
+        match bits {
+            0b0000 => Some(100_000),
+            0b0001 => Some(200_000),
+            0b0010 => Some(400_000),
+            0b0011 => Some(800_000),
+            0b0100 => Some(1_000_000),
+            0b0101 => Some(2_000_000),
+            0b0110 => Some(4_000_000),
+            0b0111 => Some(8_000_000),
+            0b1000 => Some(16_000_000),
+            0b1001 => Some(24_000_000),
+            0b1010 => Some(32_000_000),
+            0b1011 => Some(48_000_000),
+            _ => None,
         }
     }
 
     /// Configures the MSI to the specified frequency, and enables hardware
     /// auto-calibration if requested by enabling (and waiting for) the LSE.
+    ///
+    /// Panics if auto-calibration is requested, but LSE fails to start.
     pub fn configure(&self, rcc: &rcc::RegisterBlock) -> (u32, u8) {
+        self.try_configure(rcc, None).expect("LSE is not ready, MSI auto-calibration requires LSE crystal")
+    }
+
+    /// Same as `configure`, but waits for the clock at most `retries` times, or indefinitely if `None`.
+    ///
+    /// Fails also when auto-calibration is requested, but LSE fails to start.
+    pub fn try_configure(&self, rcc: &rcc::RegisterBlock, retries: Option<u32>) -> Result<(u32, u8), ClockError> {
         rcc.cr.modify(|_, w| unsafe { w.msirange().bits(self.bits()).msirgsel().set_bit() });
-        while rcc.cr.read().msirdy().bit_is_clear() {}
+        wait_ready(retries, || rcc.cr.read().msirdy().bit_is_set())?;
 
         if self.auto_cal {
-            // FIXME This... may not work? I'm not sure if I've got a board problem or using
-            // the LSE requires some precondition I'm missing. In either case, LSERDY is never
-            // set by the hardware, so auto_cal doesn't succeed.
-            rcc.apb1enr1.modify(|_, w| w.pwren().set_bit());
-
-            rcc.bdcr.modify(|_, w| w.lseon().clear_bit());
-            while rcc.bdcr.read().lserdy().bit_is_set() {}
-            rcc.bdcr.modify(|_, w| unsafe { w.lsedrv().bits(0b11).lseon().set_bit() });
-            while rcc.bdcr.read().lserdy().bit_is_clear() {}
-            rcc.cr.modify(|_, w| w.msipllen().set_bit());
+            self.enable_auto_cal(rcc)?;
         }
-        (self.freq(), 0b00)
+        Ok((self.freq(), 0b00))
+    }
+
+    /// Enables hardware auto-calibration of MSI against LSE (see 6.2.3).
+    ///
+    /// LSE is controlled by backup domain, so its write protection is lifted first.
+    /// If LSE is not running, it is started with the highest drive capability,
+    /// use `BDCR::set_lse_drive` and `BDCR::lse_enable` beforehand to choose another.
+    ///
+    /// Returns `ClockError::Timeout` if LSE fails to start within `LSE_READY_RETRIES` iterations,
+    /// in which case MSI runs without calibration.
+    pub fn enable_auto_cal(&self, rcc: &rcc::RegisterBlock) -> Result<(), ClockError> {
+        // BDCR is write protected, until DBP is set, which in turn requires clock of PWR
+        rcc.apb1enr1.modify(|_, w| w.pwren().set_bit());
+        let pwr = unsafe { &*PWR::ptr() };
+        pwr.cr1.modify(|_, w| w.dbp().set_bit());
+        while pwr.cr1.read().dbp().bit_is_clear() {}
+
+        if rcc.bdcr.read().lserdy().bit_is_clear() {
+            // Drive capability can be increased only while LSE is off
+            if rcc.bdcr.read().lseon().bit_is_clear() {
+                rcc.bdcr.modify(|_, w| unsafe { w.lsedrv().bits(LseDrive::High.bits()) });
+            }
+            rcc.bdcr.modify(|_, w| w.lseon().set_bit());
+
+            wait_ready(Some(LSE_READY_RETRIES), || rcc.bdcr.read().lserdy().bit_is_set())?;
+        }
+
+        // MSIPLLEN must be set only once LSE is ready
+        rcc.cr.modify(|_, w| w.msipllen().set_bit());
+        Ok(())
     }
 }
 
 impl InputClock for MediumSpeedInternalRC {
     fn freq(&self) -> u32 {
-        self.freq
+        // Range can be invalid only if reserved bits are read from the register
+        Self::range_freq(self.range).unwrap_or(0)
     }
 }
 
@@ -130,9 +296,16 @@ impl HighSpeedExternalOSC {
     ///
     /// (Should this also configure the pin?)
     pub fn configure(&self, rcc: &rcc::RegisterBlock) -> (u32, u8) {
+        self.try_configure(rcc, None).unwrap()
+    }
+
+    /// Same as `configure`, but waits for the oscillator at most `retries` times, or indefinitely if `None`.
+    ///
+    /// As HSE depends on external crystal, this is the recommended way to turn it on.
+    pub fn try_configure(&self, rcc: &rcc::RegisterBlock, retries: Option<u32>) -> Result<(u32, u8), ClockError> {
         rcc.cr.modify(|_, w| w.hseon().set_bit());
-        while rcc.cr.read().hserdy().bit_is_clear() {}
-        (self.freq(), 0b10)
+        wait_ready(retries, || rcc.cr.read().hserdy().bit_is_set())?;
+        Ok((self.freq(), 0b10))
     }
 }
 
@@ -173,6 +346,317 @@ impl RtcClkSource {
     }
 }
 
+/// Selectable clocks for the low-speed clock output (LSCO)
+#[derive(Copy, Clone)]
+pub enum LscoClkSource {
+    /// LSCO output disabled
+    None,
+    /// Internal 32 kHz RC
+    LSI,
+    /// External 32.768 kHz oscillator
+    LSE,
+}
+
+/// Drive capability of the LSE oscillator
+///
+/// Higher drive is needed by crystals with higher ESR or load capacitance,
+/// while too high drive might over-drive the crystal and increases consumption.
+#[derive(Copy, Clone)]
+pub enum LseDrive {
+    /// Lower driving capability
+    Low = 0b00,
+    /// Medium low driving capability
+    MediumLow = 0b01,
+    /// Medium high driving capability
+    MediumHigh = 0b10,
+    /// Higher driving capability
+    High = 0b11,
+}
+
+impl LseDrive {
+    /// Return bits for setting LSEDRV (see 6.4.29)
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// Selectable kernel clocks of the low-power timers
+#[derive(Copy, Clone)]
+pub enum LpTimClkSource {
+    /// APB1 clock
+    PCLK = 0b00,
+    /// Internal 32 kHz RC
+    LSI = 0b01,
+    /// High speed internal 16 MHz RC
+    HSI16 = 0b10,
+    /// External 32.768 kHz oscillator
+    LSE = 0b11,
+}
+
+impl LpTimClkSource {
+    /// Returns the frequency of the clock, given frequency of APB1.
+    pub fn freq(&self, pclk1: u32) -> u32 {
+        match *self {
+            LpTimClkSource::PCLK => pclk1,
+            LpTimClkSource::LSI => 32_000,
+            LpTimClkSource::HSI16 => 16_000_000,
+            LpTimClkSource::LSE => 32_768,
+        }
+    }
+
+    /// Return bits for setting LPTIMxSEL (see 6.4.28)
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// Selectable kernel clocks of the I2C
+#[derive(Copy, Clone)]
+pub enum I2cClkSource {
+    /// APB1 clock
+    PCLK1 = 0b00,
+    /// System clock
+    SYSCLK = 0b01,
+    /// High speed internal 16 MHz RC
+    ///
+    /// Unlike others, it allows I2C to keep running and wake up MCU in Stop mode,
+    /// given that HSI16 is turned on by `CR::enable_hsi16`.
+    HSI16 = 0b10,
+}
+
+impl I2cClkSource {
+    /// Returns the frequency of the clock, given frequencies of APB1 and SYSCLK.
+    pub fn freq(&self, pclk1: u32, sysclk: u32) -> u32 {
+        match *self {
+            I2cClkSource::PCLK1 => pclk1,
+            I2cClkSource::SYSCLK => sysclk,
+            I2cClkSource::HSI16 => 16_000_000,
+        }
+    }
+
+    /// Return bits for setting I2CxSEL (see 6.4.28)
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+
+    pub(crate) fn from_bits(bits: u8) -> Self {
+        match bits {
+            0b01 => I2cClkSource::SYSCLK,
+            0b10 => I2cClkSource::HSI16,
+            // 0b11 is reserved
+            _ => I2cClkSource::PCLK1,
+        }
+    }
+}
+
+/// Selectable clocks for the 48 MHz domain (CLK48 signal), which drives USB FS, RNG and SDMMC
+#[derive(Clone, Copy)]
+pub enum Clk48ClkSource {
+    /// High speed internal 48 MHz RC
+    #[cfg(feature = "STM32L496AG")]
+    HSI48(HighSpeedInternal48RC),
+    /// PLL48M1CLK signal (Q output of PLL), see `PLLClkOutput::with_q`
+    ///
+    /// When PLL is running already, e.g. driving SYSCLK, only its Q output is enabled,
+    /// so it must be the same configuration, see `PLLClkOutput::enable_q`.
+    PLLQ(PLLClkOutput),
+    /// PLL48M2CLK signal (Q output of PLLSAI1), see `PllSai1::with_q`
+    PLLSAI1Q(PllSai1),
+    /// Medium speed internal RC, which must run at 48 MHz
+    ///
+    /// Its accuracy is sufficient only with auto-calibration against LSE.
+    /// When MSI also drives SYSCLK or PLL, its range is not changed, so it must already run at 48 MHz.
+    MSI(MediumSpeedInternalRC),
+}
+
+impl Clk48ClkSource {
+    /// Checks that the selected clock provides 48 MHz, returning its frequency.
+    ///
+    /// Fails if it is off the 48 MHz by more than `CLK48_TOLERANCE`, or its output is not enabled.
+    pub fn check(&self) -> Result<u32, ClockError> {
+        check_clk48(self.freq())
+    }
+
+    /// Turns on the selected clock, returning its frequency and bits for setting CLK48SEL (see 6.4.28)
+    ///
+    /// Panics if PLL is selected without its Q output, or MSI is selected with range different from
+    /// the one it already drives SYSCLK or PLL with.
+    pub fn configure(&self, rcc: &rcc::RegisterBlock) -> (u32, u8) {
+        self.try_configure(rcc, None).unwrap()
+    }
+
+    /// Same as `configure`, but waits for the clock at most `retries` times, or indefinitely if `None`.
+    ///
+    /// Returns `ClockError::InvalidFrequency` instead of panicking for MSI in use with different range,
+    /// or PLL, which is running with configuration different from `PLLQ` one (see `PLLClkOutput::enable_q`).
+    pub fn try_configure(&self, rcc: &rcc::RegisterBlock, retries: Option<u32>) -> Result<(u32, u8), ClockError> {
+        match *self {
+            #[cfg(feature = "STM32L496AG")]
+            Clk48ClkSource::HSI48(s) => Ok((s.try_configure(rcc, retries)?, 0b00)),
+            Clk48ClkSource::PLLQ(s) => {
+                let freq = s.freq_q().expect("PLL Q output must be enabled to drive CLK48");
+                // PLL is running already, when it drives SYSCLK
+                match rcc.cr.read().pllrdy().bit_is_set() {
+                    true => {
+                        let _freq = s.enable_q(rcc)?;
+                    },
+                    false => {
+                        let _c = s.try_configure(rcc, retries)?;
+                    },
+                }
+                Ok((freq, 0b10))
+            },
+            Clk48ClkSource::PLLSAI1Q(s) => {
+                let (_, freq, _) = s.try_configure(rcc, retries)?;
+                Ok((freq.expect("PLLSAI1 Q output must be enabled to drive CLK48"), 0b01))
+            },
+            Clk48ClkSource::MSI(s) => match MediumSpeedInternalRC::is_in_use(rcc) {
+                // Changing range would change SYSCLK or PLL behind the back of `Clocks` and flash latency
+                true if MediumSpeedInternalRC::current_range(rcc) != s.bits() => Err(ClockError::InvalidFrequency),
+                true => Ok((s.freq(), 0b11)),
+                false => {
+                    let (freq, _) = s.try_configure(rcc, retries)?;
+                    Ok((freq, 0b11))
+                },
+            },
+        }
+    }
+}
+
+impl InputClock for Clk48ClkSource {
+    fn freq(&self) -> u32 {
+        match *self {
+            #[cfg(feature = "STM32L496AG")]
+            Clk48ClkSource::HSI48(s) => s.freq(),
+            Clk48ClkSource::PLLQ(s) => s.freq_q().unwrap_or(0),
+            Clk48ClkSource::PLLSAI1Q(s) => s.freq_q().unwrap_or(0),
+            Clk48ClkSource::MSI(s) => s.freq(),
+        }
+    }
+}
+
+/// Selectable kernel clocks of the ADC
+///
+/// Alternatively ADC can be clocked synchronously by HCLK, which is selected by CKMODE
+/// of ADC's common control register, in which case kernel clock is not needed.
+#[derive(Clone, Copy)]
+pub enum AdcClkSource {
+    /// No clock selected
+    None,
+    /// PLLADC1CLK signal (R output of PLLSAI1), see `PllSai1::with_r`
+    PLLSAI1R(PllSai1),
+    /// SYSCLK signal
+    SYSCLK,
+}
+
+impl AdcClkSource {
+    /// Returns the frequency of the clock, given frequency of SYSCLK.
+    pub fn freq(&self, sysclk: u32) -> Option<u32> {
+        match *self {
+            AdcClkSource::None => None,
+            AdcClkSource::PLLSAI1R(s) => s.freq_r(),
+            AdcClkSource::SYSCLK => Some(sysclk),
+        }
+    }
+
+    /// Return bits for setting ADCSEL (see 6.4.28)
+    pub fn bits(&self) -> u8 {
+        match *self {
+            AdcClkSource::None => 0b00,
+            AdcClkSource::PLLSAI1R(_) => 0b01,
+            AdcClkSource::SYSCLK => 0b11,
+        }
+    }
+}
+
+/// Selectable kernel clocks of the SAI1
+///
+/// PLLSAI2 and P output of main PLL are not configured by this crate, so they must be set up
+/// separately and only their frequency is carried.
+#[derive(Clone, Copy)]
+pub enum Sai1ClkSource {
+    /// PLLSAI1CLK signal (P output of PLLSAI1), see `PllSai1::with_p`
+    PLLSAI1P(PllSai1),
+    /// PLLSAI2CLK signal (P output of PLLSAI2), with its frequency
+    PLLSAI2P(u32),
+    /// PLLSAI3CLK signal (P output of main PLL), with its frequency
+    PLLP(u32),
+    /// External clock on SAI1_EXTCLK pin, with its frequency
+    ///
+    /// The pin must be configured separately.
+    EXTCLK(u32),
+}
+
+impl Sai1ClkSource {
+    /// Returns the frequency of the clock.
+    pub fn freq(&self) -> Option<u32> {
+        match *self {
+            Sai1ClkSource::PLLSAI1P(s) => s.freq_p(),
+            Sai1ClkSource::PLLSAI2P(freq) | Sai1ClkSource::PLLP(freq) | Sai1ClkSource::EXTCLK(freq) => Some(freq),
+        }
+    }
+
+    /// Return bits for setting SAI1SEL (see 6.4.28)
+    pub fn bits(&self) -> u8 {
+        match *self {
+            Sai1ClkSource::PLLSAI1P(_) => 0b00,
+            Sai1ClkSource::PLLSAI2P(_) => 0b01,
+            Sai1ClkSource::PLLP(_) => 0b10,
+            Sai1ClkSource::EXTCLK(_) => 0b11,
+        }
+    }
+}
+
+/// Selectable clocks for the microcontroller clock output (MCO)
+#[derive(Clone, Copy)]
+pub enum McoClkSource {
+    /// MCO output disabled
+    None = 0b000,
+    /// SYSCLK signal
+    SYSCLK = 0b001,
+    /// Medium speed internal RC
+    MSI = 0b010,
+    /// High speed internal 16 MHz RC
+    HSI16 = 0b011,
+    /// High-speed external oscillator
+    HSE = 0b100,
+    /// Main PLLCLK signal
+    PLL = 0b101,
+    /// Internal 32 kHz RC
+    LSI = 0b110,
+    /// External 32.768 kHz oscillator
+    LSE = 0b111,
+}
+
+impl McoClkSource {
+    /// Return bits for setting MCOSEL (see 6.4.3)
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// Division factor of the microcontroller clock output (MCO)
+#[derive(Clone, Copy)]
+pub enum McoPrescaler {
+    /// MCO is not divided
+    Div1 = 0b000,
+    /// MCO is divided by 2
+    Div2 = 0b001,
+    /// MCO is divided by 4
+    Div4 = 0b010,
+    /// MCO is divided by 8
+    Div8 = 0b011,
+    /// MCO is divided by 16
+    Div16 = 0b100,
+}
+
+impl McoPrescaler {
+    /// Return bits for setting MCOPRE (see 6.4.3)
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+}
+
 /// Selectable clocks for the SYSCLK signal (HCLK bus)
 #[derive(Clone, Copy)]
 pub enum SysClkSource {
@@ -206,6 +690,7 @@ pub struct PLLClkOutput {
     pub m: u8,
     n: u8,
     r: u8,
+    q: Option<u8>,
     f: u32,
 }
 
@@ -221,24 +706,67 @@ impl PLLClkOutput {
         assert!(n > 7 && n < 87);
         assert!(r == 2 || r == 4 || r == 6 || r == 8);
         let f = src.freq() / m as u32 * n as u32 / r as u32;
-        assert!(f < super::SYS_CLOCK_MAX);
+        assert!(f <= super::SYS_CLOCK_MAX);
+
+        PLLClkOutput { src, m, n, r, q: None, f }
+    }
+
+    /// Enables the PLL48M1CLK output, which is divided by `q` and can drive the 48 MHz domain.
+    ///
+    /// Panics if `q` is invalid or the output frequency is not 48 MHz (see `check_clk48`).
+    pub fn with_q(mut self, q: u8) -> Self {
+        assert!(q == 2 || q == 4 || q == 6 || q == 8);
+        let _freq = check_clk48(self.src.freq() / self.m as u32 * self.n as u32 / q as u32).expect("PLL Q output must be 48 MHz");
+
+        self.q = Some(q);
+        self
+    }
 
-        PLLClkOutput { src, m, n, r, f }
+    /// Returns the frequency of the PLL48M1CLK output, if it is enabled.
+    pub fn freq_q(&self) -> Option<u32> {
+        self.q.map(|q| self.src.freq() / self.m as u32 * self.n as u32 / q as u32)
     }
 
-    /// Configure the PLL to enable the PLLCLK output. This explicitly does not (yet?)
-    /// support any PLL other than `PLL`, and no other outputs than `PLLCLK`, so this is
-    /// not suitable for driving e.g. USB.
+    /// Configure the PLL to enable the PLLCLK output, and PLL48M1CLK output if `q` is set.
+    /// This explicitly does not (yet?) support any PLL other than `PLL`.
     pub fn configure(&self, rcc: &rcc::RegisterBlock) -> (u32, u8) {
-        let pllsrc_bits = self.src.configure(rcc);
+        self.try_configure(rcc, None).unwrap()
+    }
+
+    /// Same as `configure`, but waits for PLL and its source at most `retries` times each,
+    /// or indefinitely if `None`.
+    pub fn try_configure(&self, rcc: &rcc::RegisterBlock, retries: Option<u32>) -> Result<(u32, u8), ClockError> {
+        let pllsrc_bits = self.src.try_configure(rcc, retries)?;
         rcc.cr.modify(|_, w| w.pllon().clear_bit());
-        while rcc.cr.read().pllrdy().bit_is_set() {}
+        wait_ready(retries, || rcc.cr.read().pllrdy().bit_is_clear())?;
         rcc.pllcfgr
-            .modify(|_, w| unsafe { w.pllsrc().bits(pllsrc_bits).pllm().bits(self.m - 1).plln().bits(self.n).pllr().bits(self.r) });
+            .modify(|_, w| unsafe { w.pllsrc().bits(pllsrc_bits).pllm().bits(self.m - 1).plln().bits(self.n).pllr().bits(self.r / 2 - 1) });
+        if let Some(q) = self.q {
+            rcc.pllcfgr.modify(|_, w| unsafe { w.pllq().bits(q / 2 - 1) });
+        }
         rcc.cr.modify(|_, w| w.pllon().set_bit());
-        while rcc.cr.read().pllrdy().bit_is_clear() {}
-        rcc.pllcfgr.modify(|_, w| w.pllren().set_bit());
-        (self.freq(), 0b11)
+        wait_ready(retries, || rcc.cr.read().pllrdy().bit_is_set())?;
+        rcc.pllcfgr.modify(|_, w| w.pllren().set_bit().pllqen().bit(self.q.is_some()));
+        Ok((self.freq(), 0b11))
+    }
+
+    /// Enables PLL48M1CLK output of already running PLL, returning its frequency.
+    ///
+    /// Q divider can be changed only while PLL is off, therefore running PLL must be
+    /// of the same configuration, otherwise `ClockError::InvalidFrequency` is returned.
+    pub fn enable_q(&self, rcc: &rcc::RegisterBlock) -> Result<u32, ClockError> {
+        let q = self.q.ok_or(ClockError::InvalidFrequency)?;
+        let pllcfgr = rcc.pllcfgr.read();
+        let is_same = pllcfgr.pllsrc().bits() == self.src.bits()
+            && pllcfgr.pllm().bits() == self.m - 1
+            && pllcfgr.plln().bits() == self.n
+            && pllcfgr.pllq().bits() == q / 2 - 1;
+        if !is_same {
+            return Err(ClockError::InvalidFrequency);
+        }
+
+        rcc.pllcfgr.modify(|_, w| w.pllqen().set_bit());
+        Ok(self.src.freq() / self.m as u32 * self.n as u32 / q as u32)
     }
 }
 
@@ -248,14 +776,111 @@ impl InputClock for PLLClkOutput {
     }
 }
 
-//
-/// PLLADC2CLK output of PLLSAI2
-// #[derive(Clone, Copy)]
-// pub struct PLLADC2Clk {
-// src: PLLClkSource,
-// ...,
-// }
-//
+/// Frequencies of P, Q and R outputs of PLLSAI1, `None` when output is disabled
+pub type PllSai1Freqs = (Option<u32>, Option<u32>, Option<u32>);
+
+/// PLLSAI1 module, which provides clocks for SAI1/SAI2 (P output), 48 MHz domain (Q output)
+/// and ADC (R output)
+///
+/// PLLSAI1 shares input source and "M" prescaler with main PLL, so when both are used
+/// they must be the same.
+#[derive(Clone, Copy)]
+pub struct PllSai1 {
+    /// The input source of the PLL modules
+    pub src: PLLClkSource,
+    /// The initial prescaler value into all PLLs
+    pub m: u8,
+    n: u8,
+    p: Option<u8>,
+    q: Option<u8>,
+    r: Option<u8>,
+}
+
+impl PllSai1 {
+    /// Create a new PLLSAI1 with all outputs disabled.
+    ///
+    /// The arguments refer to the scale factors described in Figs. 15 and 16 of the reference
+    /// manual, and end up in the PLLM and PLLSAI1N fields of the PLLCFGR and PLLSAI1CFGR registers.
+    pub fn new(src: PLLClkSource, m: u8, n: u8) -> Self {
+        assert!(m > 0 && m < 9);
+        assert!(n > 7 && n < 87);
+
+        PllSai1 { src, m, n, p: None, q: None, r: None }
+    }
+
+    /// Enables PLLSAI1CLK output, divided by `p` which is either 7 or 17.
+    pub fn with_p(mut self, p: u8) -> Self {
+        assert!(p == 7 || p == 17);
+        self.p = Some(p);
+        self
+    }
+
+    /// Enables PLL48M2CLK output, divided by `q`.
+    pub fn with_q(mut self, q: u8) -> Self {
+        assert!(q == 2 || q == 4 || q == 6 || q == 8);
+        self.q = Some(q);
+        self
+    }
+
+    /// Enables PLLADC1CLK output, divided by `r`.
+    pub fn with_r(mut self, r: u8) -> Self {
+        assert!(r == 2 || r == 4 || r == 6 || r == 8);
+        self.r = Some(r);
+        self
+    }
+
+    #[inline]
+    fn vco(&self) -> u32 {
+        self.src.freq() / self.m as u32 * self.n as u32
+    }
+
+    /// Returns the frequency of the PLLSAI1CLK output, if it is enabled.
+    pub fn freq_p(&self) -> Option<u32> {
+        self.p.map(|p| self.vco() / p as u32)
+    }
+
+    /// Returns the frequency of the PLL48M2CLK output, if it is enabled.
+    pub fn freq_q(&self) -> Option<u32> {
+        self.q.map(|q| self.vco() / q as u32)
+    }
+
+    /// Returns the frequency of the PLLADC1CLK output, if it is enabled.
+    pub fn freq_r(&self) -> Option<u32> {
+        self.r.map(|r| self.vco() / r as u32)
+    }
+
+    /// Configures PLLSAI1 and enables its selected outputs, returning frequencies of P, Q and R outputs.
+    ///
+    /// Input of PLLs is configured only when main PLL is not running, otherwise it is expected to be the same.
+    pub fn configure(&self, rcc: &rcc::RegisterBlock) -> PllSai1Freqs {
+        self.try_configure(rcc, None).unwrap()
+    }
+
+    /// Same as `configure`, but waits for PLLSAI1 and its source at most `retries` times each,
+    /// or indefinitely if `None`.
+    pub fn try_configure(&self, rcc: &rcc::RegisterBlock, retries: Option<u32>) -> Result<PllSai1Freqs, ClockError> {
+        if rcc.cr.read().pllrdy().bit_is_clear() {
+            let pllsrc_bits = self.src.try_configure(rcc, retries)?;
+            rcc.pllcfgr.modify(|_, w| unsafe { w.pllsrc().bits(pllsrc_bits).pllm().bits(self.m - 1) });
+        } else {
+            debug_assert_eq!(rcc.pllcfgr.read().pllm().bits(), self.m - 1);
+        }
+
+        rcc.cr.modify(|_, w| w.pllsai1on().clear_bit());
+        wait_ready(retries, || rcc.cr.read().pllsai1rdy().bit_is_clear())?;
+        rcc.pllsai1cfgr.modify(|_, w| unsafe {
+            w.pllsai1n().bits(self.n)
+             .pllsai1p().bit(self.p == Some(17))
+             .pllsai1q().bits(self.q.map(|q| q / 2 - 1).unwrap_or(0))
+             .pllsai1r().bits(self.r.map(|r| r / 2 - 1).unwrap_or(0))
+        });
+        rcc.cr.modify(|_, w| w.pllsai1on().set_bit());
+        wait_ready(retries, || rcc.cr.read().pllsai1rdy().bit_is_set())?;
+        rcc.pllsai1cfgr.modify(|_, w| w.pllsai1pen().bit(self.p.is_some()).pllsai1qen().bit(self.q.is_some()).pllsai1ren().bit(self.r.is_some()));
+
+        Ok((self.freq_p(), self.freq_q(), self.freq_r()))
+    }
+}
 
 /// Selectable PLL module input sources
 #[derive(Clone, Copy)]
@@ -271,22 +896,37 @@ pub enum PLLClkSource {
 }
 
 impl PLLClkSource {
+    /// Returns PLLSRC bits (6.4.4) of the source.
+    pub fn bits(&self) -> u8 {
+        match self {
+            PLLClkSource::None => 0b00,
+            PLLClkSource::MSI(_) => 0b01,
+            PLLClkSource::HSI16(_) => 0b10,
+            PLLClkSource::HSE(_) => 0b11,
+        }
+    }
+
     /// This configures the input to the PLL. It's usually only called by
     /// PLLClkOutput::configure.
     pub fn configure(&self, rcc: &rcc::RegisterBlock) -> u8 {
+        self.try_configure(rcc, None).unwrap()
+    }
+
+    /// Same as `configure`, but waits for the clock at most `retries` times, or indefinitely if `None`.
+    pub fn try_configure(&self, rcc: &rcc::RegisterBlock, retries: Option<u32>) -> Result<u8, ClockError> {
         match self {
-            PLLClkSource::None => 0b00,
+            PLLClkSource::None => Ok(0b00),
             PLLClkSource::MSI(s) => {
-                let _c = s.configure(rcc);
-                0b01
+                let _c = s.try_configure(rcc, retries)?;
+                Ok(0b01)
             },
             PLLClkSource::HSI16(s) => {
-                let _c = s.configure(rcc);
-                0b10
+                let _c = s.try_configure(rcc, retries)?;
+                Ok(0b10)
             },
             PLLClkSource::HSE(s) => {
-                let _c = s.configure(rcc);
-                0b11
+                let _c = s.try_configure(rcc, retries)?;
+                Ok(0b11)
             },
         }
     }
@@ -1,17 +1,25 @@
 //! Reset and Clock Control
 
-// TODO right now the various configure functions reach into rcc directly. This is bad. Add an
-// opaque CR member to RCC, and add methods to CR and BDCR. They should probably take clock source
-// variant arguments.
+// TODO right now the various configure functions reach into rcc directly. This is bad. Move
+// them into methods of CR and BDCR. They should probably take clock source variant arguments.
 
 #![deny(missing_docs, unused_results)]
 
 use stm32l4x6::{rcc, PWR, RCC};
+use stm32l4x6::{
+    ADC123_COMMON, CAN, CRC, DAC, DFSDM, DMA1, DMA2, FMC, GPIOA, GPIOB, GPIOC, GPIOD, GPIOE, GPIOF, GPIOG, GPIOH, I2C1, I2C2, I2C3, LCD,
+    LPTIM1, LPTIM2, LPUART1, OPAMP, OTG_FS_GLOBAL, QUADSPI, RNG, SAI1, SAI2, SDMMC, SPI1, SPI2, SPI3, SWPMI1, SYSCFG, TIM1, TIM15, TIM16,
+    TIM17, TIM2, TIM3, TIM4, TIM5, TIM6, TIM7, TIM8, TSC, UART4, UART5, USART1, USART2, USART3,
+};
 
 use crate::common::Constrain;
 use crate::flash::ACR;
+use crate::gpio::{AF0, PA8};
+use crate::power::VoltageRange;
 use crate::time::Hertz;
 
+use self::clocking::InputClock;
+
 pub mod clocking;
 
 impl Constrain<Rcc> for RCC {
@@ -23,8 +31,9 @@ impl Constrain<Rcc> for RCC {
     /// it again. This is to enable changing LSE- and RTC-related settings.
     fn constrain(self) -> Rcc {
         // Enable write access to the BDCR; this is necessary to enable the LSE and change RTC
-        // settings.
+        // settings. PWR registers are accessible only when its clock is enabled.
         unsafe {
+            (*RCC::ptr()).apb1enr1.modify(|_, w| w.pwren().set_bit());
             (*PWR::ptr()).cr1.modify(|_, w| w.dbp().set_bit());
         }
         // Write access is (similarly) disabled in CFGR::freeze()
@@ -33,13 +42,20 @@ impl Constrain<Rcc> for RCC {
             ahb: AHB(()),
             apb1: APB1(()),
             apb2: APB2(()),
+            cr: CR(()),
+            icscr: ICSCR(()),
             bdcr: BDCR(()),
             csr: CSR(()),
+            ccipr: CCIPR(()),
             cfgr: CFGR {
                 hclk: None,
                 pclk1: None,
                 pclk2: None,
-                sysclk: clocking::SysClkSource::MSI(clocking::MediumSpeedInternalRC::new(4_000_000, false)),
+                // MSI runs at 4 MHz after reset
+                sysclk: clocking::SysClkSource::MSI(clocking::MediumSpeedInternalRC::from_range(0b0110, false)),
+                clk48: None,
+                adcclk: None,
+                sai1clk: None,
             },
         }
     }
@@ -53,14 +69,257 @@ pub struct Rcc {
     pub apb1: APB1,
     /// APB2 peripheral registers.
     pub apb2: APB2,
+    /// Clock control register.
+    pub cr: CR,
+    /// Internal clock sources calibration register.
+    pub icscr: ICSCR,
     /// Backup domain registers.
     pub bdcr: BDCR,
     /// Control/status register.
     pub csr: CSR,
+    /// Peripherals independent clock configuration register.
+    pub ccipr: CCIPR,
     /// HW clock configuration.
     pub cfgr: CFGR,
 }
 
+impl Rcc {
+    /// Reads the active clock configuration from the hardware.
+    ///
+    /// It is useful when clocks have been configured without `freeze`, e.g. by bootloader.
+    /// As frequency of HSE cannot be detected, it must be provided as `hse` when HSE is in use,
+    /// otherwise clocks driven by HSE are reported as 0 Hz.
+    pub fn current_clocks(&self, hse: Option<clocking::HighSpeedExternalOSC>) -> Clocks {
+        let rcc = unsafe { &*RCC::ptr() };
+        let cr = rcc.cr.read();
+
+        let msi_range = clocking::MediumSpeedInternalRC::current_range(rcc);
+        let msi = clocking::MediumSpeedInternalRC::from_range(msi_range, cr.msipllen().bit());
+        let hsi = clocking::HighSpeedInternal16RC {
+            always_on: cr.hsikeron().bit(),
+            auto_start: cr.hsiasfs().bit(),
+        };
+
+        let pllcfgr = rcc.pllcfgr.read();
+        let pll_src = match pllcfgr.pllsrc().bits() {
+            0b01 => clocking::PLLClkSource::MSI(msi),
+            0b10 => clocking::PLLClkSource::HSI16(hsi),
+            0b11 => match hse {
+                Some(hse) => clocking::PLLClkSource::HSE(hse),
+                None => clocking::PLLClkSource::None,
+            },
+            _ => clocking::PLLClkSource::None,
+        };
+        let pll_psc = pllcfgr.pllm().bits() + 1;
+        // Q and R dividers are encoded as 2, 4, 6 or 8
+        let pll_div = |vco: u32, bits: u8| Hertz(vco / ((bits as u32 + 1) * 2));
+
+        let pll_vco = pll_src.freq() / pll_psc as u32 * pllcfgr.plln().bits() as u32;
+        let is_pll_ready = cr.pllrdy().bit_is_set();
+        let pllq = match is_pll_ready && pllcfgr.pllqen().bit_is_set() {
+            true => Some(pll_div(pll_vco, pllcfgr.pllq().bits())),
+            false => None,
+        };
+
+        let pllsai1cfgr = rcc.pllsai1cfgr.read();
+        let pllsai1_vco = pll_src.freq() / pll_psc as u32 * pllsai1cfgr.pllsai1n().bits() as u32;
+        let is_pllsai1_ready = cr.pllsai1rdy().bit_is_set();
+        let pllsai1q = match is_pllsai1_ready && pllsai1cfgr.pllsai1qen().bit_is_set() {
+            true => Some(pll_div(pllsai1_vco, pllsai1cfgr.pllsai1q().bits())),
+            false => None,
+        };
+        let pllsai1r = match is_pllsai1_ready && pllsai1cfgr.pllsai1ren().bit_is_set() {
+            true => Some(pll_div(pllsai1_vco, pllsai1cfgr.pllsai1r().bits())),
+            false => None,
+        };
+        // P divider is either 7 or 17
+        let pllsai1p = match is_pllsai1_ready && pllsai1cfgr.pllsai1pen().bit_is_set() {
+            true if pllsai1cfgr.pllsai1p().bit_is_set() => Some(Hertz(pllsai1_vco / 17)),
+            true => Some(Hertz(pllsai1_vco / 7)),
+            false => None,
+        };
+
+        let cfgr = rcc.cfgr.read();
+        let sys_clock = match cfgr.sws().bits() {
+            0b00 => msi.freq(),
+            0b01 => 16_000_000,
+            0b10 => hse.map(|hse| hse.freq()).unwrap_or(0),
+            _ => pll_div(pll_vco, pllcfgr.pllr().bits()).0,
+        };
+
+        let hpre = CFGR::hpre_div(cfgr.hpre().bits());
+        let ahb = sys_clock / hpre;
+        let ppre1 = CFGR::ppre_div(cfgr.ppre1().bits());
+        let ppre2 = CFGR::ppre_div(cfgr.ppre2().bits());
+
+        let ccipr = rcc.ccipr.read();
+        let clk48 = match ccipr.clk48sel().bits() {
+            #[cfg(feature = "STM32L496AG")]
+            0b00 if clocking::HighSpeedInternal48RC::is_ready(rcc) => Some(Hertz(48_000_000)),
+            0b01 => pllsai1q,
+            0b10 => pllq,
+            0b11 if cr.msirdy().bit_is_set() => Some(Hertz(msi.freq())),
+            _ => None,
+        };
+        let adcclk = match ccipr.adcsel().bits() {
+            0b01 => pllsai1r,
+            0b11 => Some(Hertz(sys_clock)),
+            _ => None,
+        };
+        // Frequencies of external clock, PLLSAI2 and P output of main PLL are not tracked
+        let sai1clk = match ccipr.sai1sel().bits() {
+            0b00 => pllsai1p,
+            _ => None,
+        };
+
+        Clocks {
+            hclk: Hertz(ahb),
+            pclk1: Hertz(ahb / ppre1 as u32),
+            pclk2: Hertz(ahb / ppre2 as u32),
+            sysclk: Hertz(sys_clock),
+            pll_src: match is_pll_ready {
+                true => Some(pll_src),
+                false => None,
+            },
+            pll_psc: match is_pll_ready {
+                true => Some(pll_psc),
+                false => None,
+            },
+            pllq,
+            hpre: hpre as u16,
+            ppre1,
+            ppre2,
+            clk48,
+            adcclk,
+            sai1clk,
+        }
+    }
+}
+
+/// Peripheral, which clock is gated on one of the buses
+///
+/// Provides enable and reset sequence to the drivers, see `AHB::enable_and_reset`,
+/// `APB1::enable_and_reset` and `APB2::enable_and_reset`.
+pub trait PeripheralEnable {
+    /// Register access of the bus
+    type Bus;
+
+    /// Enables clock of the peripheral
+    fn enable(bus: &mut Self::Bus);
+    /// Disables clock of the peripheral
+    fn disable(bus: &mut Self::Bus);
+    /// Resets the peripheral
+    fn reset(bus: &mut Self::Bus);
+}
+
+macro_rules! impl_peripheral_enable {
+    ($($PER:ident: $Bus:ident => {$enr:ident: $en:ident; $rstr:ident: $rst:ident},)+) => {
+        $(
+            impl PeripheralEnable for $PER {
+                type Bus = $Bus;
+
+                #[inline]
+                fn enable(bus: &mut Self::Bus) {
+                    bus.$enr().modify(|_, w| w.$en().set_bit());
+                }
+
+                #[inline]
+                fn disable(bus: &mut Self::Bus) {
+                    bus.$enr().modify(|_, w| w.$en().clear_bit());
+                }
+
+                #[inline]
+                fn reset(bus: &mut Self::Bus) {
+                    bus.$rstr().modify(|_, w| w.$rst().set_bit());
+                    bus.$rstr().modify(|_, w| w.$rst().clear_bit());
+                }
+            }
+        )+
+    }
+}
+
+macro_rules! impl_bus_enable {
+    ($($Bus:ident)+) => {
+        $(
+            impl $Bus {
+                /// Enables clock of the peripheral `P`.
+                pub fn enable<P: PeripheralEnable<Bus = Self>>(&mut self) {
+                    P::enable(self);
+                }
+
+                /// Disables clock of the peripheral `P`.
+                pub fn disable<P: PeripheralEnable<Bus = Self>>(&mut self) {
+                    P::disable(self);
+                }
+
+                /// Enables clock of the peripheral `P` and resets it.
+                pub fn enable_and_reset<P: PeripheralEnable<Bus = Self>>(&mut self) {
+                    P::enable(self);
+                    P::reset(self);
+                }
+            }
+        )+
+    }
+}
+
+impl_bus_enable!(AHB APB1 APB2);
+
+impl_peripheral_enable!(
+    DMA1: AHB => {enr1: dma1en; rstr1: dma1rst},
+    DMA2: AHB => {enr1: dma2en; rstr1: dma2rst},
+    CRC: AHB => {enr1: crcen; rstr1: crcrst},
+    TSC: AHB => {enr1: tscen; rstr1: tscrst},
+    GPIOA: AHB => {enr2: gpioaen; rstr2: gpioarst},
+    GPIOB: AHB => {enr2: gpioben; rstr2: gpiobrst},
+    GPIOC: AHB => {enr2: gpiocen; rstr2: gpiocrst},
+    GPIOD: AHB => {enr2: gpioden; rstr2: gpiodrst},
+    GPIOE: AHB => {enr2: gpioeen; rstr2: gpioerst},
+    GPIOF: AHB => {enr2: gpiofen; rstr2: gpiofrst},
+    GPIOG: AHB => {enr2: gpiogen; rstr2: gpiogrst},
+    GPIOH: AHB => {enr2: gpiohen; rstr2: gpiohrst},
+    OTG_FS_GLOBAL: AHB => {enr2: otgfsen; rstr2: otgfsrst},
+    ADC123_COMMON: AHB => {enr2: adcen; rstr2: adcrst},
+    RNG: AHB => {enr2: rngen; rstr2: rngrst},
+    FMC: AHB => {enr3: fmcen; rstr3: fmcrst},
+    QUADSPI: AHB => {enr3: qspien; rstr3: qspirst},
+    TIM2: APB1 => {enr1: tim2en; rstr1: tim2rst},
+    TIM3: APB1 => {enr1: tim3en; rstr1: tim3rst},
+    TIM4: APB1 => {enr1: tim4en; rstr1: tim4rst},
+    TIM5: APB1 => {enr1: tim5en; rstr1: tim5rst},
+    TIM6: APB1 => {enr1: tim6en; rstr1: tim6rst},
+    TIM7: APB1 => {enr1: tim7en; rstr1: tim7rst},
+    LCD: APB1 => {enr1: lcden; rstr1: lcdrst},
+    SPI2: APB1 => {enr1: spi2en; rstr1: spi2rst},
+    SPI3: APB1 => {enr1: sp3en; rstr1: spi3rst},
+    USART2: APB1 => {enr1: usart2en; rstr1: usart2rst},
+    USART3: APB1 => {enr1: usart3en; rstr1: usart3rst},
+    UART4: APB1 => {enr1: uart4en; rstr1: uart4rst},
+    UART5: APB1 => {enr1: uart5en; rstr1: uart5rst},
+    I2C1: APB1 => {enr1: i2c1en; rstr1: i2c1rst},
+    I2C2: APB1 => {enr1: i2c2en; rstr1: i2c2rst},
+    I2C3: APB1 => {enr1: i2c3en; rstr1: i2c3rst},
+    CAN: APB1 => {enr1: can1en; rstr1: can1rst},
+    PWR: APB1 => {enr1: pwren; rstr1: pwrrst},
+    DAC: APB1 => {enr1: dac1en; rstr1: dac1rst},
+    OPAMP: APB1 => {enr1: opampen; rstr1: opamprst},
+    LPTIM1: APB1 => {enr1: lptim1en; rstr1: lptim1rst},
+    LPUART1: APB1 => {enr2: lpuart1en; rstr2: lpuart1rst},
+    SWPMI1: APB1 => {enr2: swpmi1en; rstr2: swpmi1rst},
+    LPTIM2: APB1 => {enr2: lptim2en; rstr2: lptim2rst},
+    SYSCFG: APB2 => {enr: syscfgen; rstr: syscfgrst},
+    SDMMC: APB2 => {enr: sdmmcen; rstr: sdmmcrst},
+    TIM1: APB2 => {enr: tim1en; rstr: tim1rst},
+    SPI1: APB2 => {enr: spi1en; rstr: spi1rst},
+    TIM8: APB2 => {enr: tim8en; rstr: tim8rst},
+    USART1: APB2 => {enr: usart1en; rstr: usart1rst},
+    TIM15: APB2 => {enr: tim15en; rstr: tim15rst},
+    TIM16: APB2 => {enr: tim16en; rstr: tim16rst},
+    TIM17: APB2 => {enr: tim17en; rstr: tim17rst},
+    SAI1: APB2 => {enr: sai1en; rstr: sai1rst},
+    SAI2: APB2 => {enr: sai2en; rstr: sai2rst},
+    DFSDM: APB2 => {enr: dfsdmen; rstr: dfsdmrst},
+);
+
 /// AHB 1-3 register access
 pub struct AHB(());
 impl AHB {
@@ -127,6 +386,318 @@ impl APB2 {
     }
 }
 
+/// Clock control register
+///
+/// See Reference manual Ch. 6.4.1
+pub struct CR(());
+impl CR {
+    /// Return a raw pointer to the CR register
+    #[inline]
+    pub fn inner(&mut self) -> &rcc::CR {
+        unsafe { &(*RCC::ptr()).cr }
+    }
+
+    /// Turns on HSI16 as kernel clock of peripherals, independently of SYSCLK source.
+    ///
+    /// Peripherals, such as USART, I2C or LPTIM, can keep running in Stop mode, when they are clocked by HSI16
+    /// with `always_on`, or can wake up MCU with `auto_start`, in which case HSI16 is started only
+    /// on their request. The peripheral's clock needs to be selected separately, e.g. via `CCIPR`.
+    ///
+    /// See Reference manual Ch. 6.2.2
+    pub fn enable_hsi16(&mut self, hsi: clocking::HighSpeedInternal16RC) {
+        let rcc = unsafe { &*RCC::ptr() };
+        let _c = hsi.configure(rcc);
+    }
+
+    /// Enables Clock Security System on HSE.
+    ///
+    /// On failure of HSE, the hardware switches SYSCLK to HSI16, turns off HSE and PLL (if it is driven by HSE),
+    /// and raises NMI, in which `clear_css_interrupt` must be called.
+    /// As SYSCLK has been changed, `Clocks` are no longer valid, and PLL needs to be re-configured
+    /// from another source in order to continue at the original frequency.
+    ///
+    /// CSS can be disabled only by reset.
+    ///
+    /// # Panics
+    ///
+    /// If HSE is not ready yet, i.e. before `freeze` configured it.
+    ///
+    /// See Reference manual Ch. 6.2.11
+    pub fn enable_css(&mut self) {
+        assert!(self.inner().read().hserdy().bit_is_set());
+        self.inner().modify(|_, write| write.csson().set_bit());
+    }
+
+    /// Turns off HSI16, MSI and HSE, unless they are in use, to reduce current consumption.
+    ///
+    /// Oscillator is considered in use, when it drives SYSCLK, is the source of PLL in `clocks.pll_src`
+    /// or of running PLLSAI1 or PLLSAI2, or is selected for CLK48 or MCO. HSI16 is also kept,
+    /// when it is turned on for peripherals by `enable_hsi16` with `always_on`,
+    /// or is selected as kernel clock of U(S)ART, LPUART, I2C, LPTIM or SWPMI in `CCIPR`.
+    /// HSE is also kept, when it drives RTC.
+    pub fn disable_unused_clocks(&mut self, clocks: &Clocks) {
+        const MSI: u8 = 1;
+        const HSI16: u8 = 1 << 1;
+        const HSE: u8 = 1 << 2;
+
+        let rcc = unsafe { &*RCC::ptr() };
+        let cr = rcc.cr.read();
+        let cfgr = rcc.cfgr.read();
+
+        let mut in_use = match cfgr.sws().bits() {
+            0b00 => MSI,
+            0b01 => HSI16,
+            0b10 => HSE,
+            _ => 0,
+        };
+        in_use |= match clocks.pll_src {
+            Some(clocking::PLLClkSource::MSI(_)) => MSI,
+            Some(clocking::PLLClkSource::HSI16(_)) => HSI16,
+            Some(clocking::PLLClkSource::HSE(_)) => HSE,
+            _ => 0,
+        };
+        // PLLs share their source, which may be selected without the main PLL running
+        if cr.pllon().bit_is_set() || cr.pllsai1on().bit_is_set() || cr.pllsai2on().bit_is_set() {
+            in_use |= match rcc.pllcfgr.read().pllsrc().bits() {
+                0b01 => MSI,
+                0b10 => HSI16,
+                0b11 => HSE,
+                _ => 0,
+            };
+        }
+        let ccipr = rcc.ccipr.read();
+        if ccipr.clk48sel().bits() == 0b11 {
+            in_use |= MSI;
+        }
+        // Kernel clocks of peripherals select HSI16 by 0b10 (see 6.4.28)
+        let is_hsi16_kernel = [
+            ccipr.usart1sel().bits(),
+            ccipr.usart2sel().bits(),
+            ccipr.usart3sel().bits(),
+            ccipr.uart4sel().bits(),
+            ccipr.uart5sel().bits(),
+            ccipr.lpuart1sel().bits(),
+            ccipr.i2c1sel().bits(),
+            ccipr.i2c2sel().bits(),
+            ccipr.i2c3sel().bits(),
+            ccipr.lptim1sel().bits(),
+            ccipr.lptim2sel().bits(),
+        ].contains(&0b10);
+        if is_hsi16_kernel || ccipr.swpmi1sel().bit_is_set() {
+            in_use |= HSI16;
+        }
+        // RTC is clocked by HSE divided by 32 (see 6.4.29)
+        if rcc.bdcr.read().rtcsel().bits() == 0b11 {
+            in_use |= HSE;
+        }
+        in_use |= match cfgr.mcosel().bits() {
+            0b0010 => MSI,
+            0b0011 => HSI16,
+            0b0100 => HSE,
+            _ => 0,
+        };
+        if cr.hsikeron().bit_is_set() {
+            in_use |= HSI16;
+        }
+
+        self.inner().modify(|_, write| {
+            if in_use & MSI == 0 {
+                let _w = write.msion().clear_bit();
+            }
+            if in_use & HSI16 == 0 {
+                let _w = write.hsion().clear_bit();
+            }
+            if in_use & HSE == 0 {
+                let _w = write.hseon().clear_bit();
+            }
+            write
+        });
+    }
+
+    /// Switches SYSCLK to `src` at runtime, keeping prescalers of AHB and APB buses.
+    ///
+    /// Flash latency is increased before switching to higher frequency and decreased
+    /// after switching to lower one. Returned `Clocks` replace `clocks`, which are no longer valid.
+    ///
+    /// The previous source is left running.
+    ///
+    /// # Panics
+    ///
+    /// When switching to PLL, while it drives SYSCLK already, as it cannot be re-configured while running.
+    ///
+    /// When switching to MSI with range different from the current one, while MSI drives running PLL,
+    /// as PLL would change together with it, same as `set_msi_range`.
+    ///
+    /// If new SYSCLK exceeds `SYS_CLOCK_MAX`, same as `CFGR::freeze`.
+    ///
+    /// See Reference manual Ch. 6.2.6 and 3.3.3
+    pub fn switch_sysclk(&mut self, src: clocking::SysClkSource, clocks: &Clocks, acr: &mut ACR) -> Clocks {
+        let rcc = unsafe { &*RCC::ptr() };
+
+        match src {
+            clocking::SysClkSource::PLL(_) => {
+                assert!(rcc.cfgr.read().sws().bits() != 0b11, "PLL cannot be re-configured while driving SYSCLK");
+            },
+            clocking::SysClkSource::MSI(s) if clocking::MediumSpeedInternalRC::is_pll_input(rcc) => {
+                let is_same_range = clocking::MediumSpeedInternalRC::current_range(rcc) == s.bits();
+                assert!(is_same_range, "MSI cannot be changed while it drives PLL");
+            },
+            _ => (),
+        }
+        CFGR::boost_regulator(src.freq());
+        let is_range1 = VoltageRange::current() == VoltageRange::Range1;
+        CFGR::check_sys_clock(src.freq(), is_range1);
+
+        let range = VoltageRange::current();
+        let latency = ACR::latency_for(Hertz(src.freq()), range);
+        let is_faster = latency > acr.latency();
+        if is_faster {
+            let _latency = acr.set_latency_for(Hertz(src.freq()), range);
+        }
+
+        let (sys_clock, sw_bits) = match src {
+            clocking::SysClkSource::MSI(s) => s.configure(rcc),
+            clocking::SysClkSource::HSI16(s) => s.configure(rcc),
+            clocking::SysClkSource::HSE(s) => s.configure(rcc),
+            clocking::SysClkSource::PLL(s) => s.configure(rcc),
+        };
+        rcc.cfgr.modify(|_, w| unsafe { w.sw().bits(sw_bits) });
+        while rcc.cfgr.read().sws().bits() != sw_bits {}
+
+        if !is_faster {
+            let _latency = acr.set_latency_for(Hertz(sys_clock), range);
+        }
+
+        let cfgr = rcc.cfgr.read();
+        let hpre = CFGR::hpre_div(cfgr.hpre().bits());
+        let ahb = sys_clock / hpre;
+        let ppre1 = CFGR::ppre_div(cfgr.ppre1().bits());
+        let ppre2 = CFGR::ppre_div(cfgr.ppre2().bits());
+
+        Clocks {
+            hclk: Hertz(ahb),
+            pclk1: Hertz(ahb / ppre1 as u32),
+            pclk2: Hertz(ahb / ppre2 as u32),
+            sysclk: Hertz(sys_clock),
+            pll_src: match src {
+                clocking::SysClkSource::PLL(s) => Some(s.src),
+                _ => clocks.pll_src,
+            },
+            pll_psc: match src {
+                clocking::SysClkSource::PLL(s) => Some(s.m),
+                _ => clocks.pll_psc,
+            },
+            pllq: match src {
+                clocking::SysClkSource::PLL(s) => s.freq_q().map(Hertz),
+                _ => clocks.pllq,
+            },
+            hpre: hpre as u16,
+            ppre1,
+            ppre2,
+            clk48: clocks.clk48,
+            adcclk: match clocks.adcclk {
+                Some(_) if rcc.ccipr.read().adcsel().bits() == 0b11 => Some(Hertz(sys_clock)),
+                adcclk => adcclk,
+            },
+            sai1clk: clocks.sai1clk,
+        }
+    }
+
+    /// Changes frequency range of MSI at runtime, returning new clock frequencies.
+    ///
+    /// When MSI drives SYSCLK, flash latency is adjusted as by `switch_sysclk`,
+    /// otherwise `clocks` stay the same. Hardware auto-calibration is kept as is.
+    ///
+    /// # Panics
+    ///
+    /// If `freq` is not a valid MSI range (see 6.2.3), or MSI drives running PLL.
+    pub fn set_msi_range(&mut self, freq: u32, clocks: &Clocks, acr: &mut ACR) -> Clocks {
+        let rcc = unsafe { &*RCC::ptr() };
+
+        assert!(!clocking::MediumSpeedInternalRC::is_pll_input(rcc), "MSI cannot be changed while it drives PLL");
+
+        let msi = clocking::MediumSpeedInternalRC::new(freq, false).expect("Invalid MSI range");
+        if rcc.cfgr.read().sws().bits() == 0b00 {
+            return self.switch_sysclk(clocking::SysClkSource::MSI(msi), clocks, acr);
+        }
+
+        // MSIRANGE can be changed only while MSI is either off or ready
+        let is_on = rcc.cr.read().msion().bit_is_set();
+        while is_on && rcc.cr.read().msirdy().bit_is_clear() {}
+        rcc.cr.modify(|_, w| unsafe { w.msirange().bits(msi.bits()).msirgsel().set_bit() });
+        while is_on && rcc.cr.read().msirdy().bit_is_clear() {}
+
+        *clocks
+    }
+
+    /// Returns whether HSE failure has been detected by Clock Security System.
+    pub fn is_css_pending() -> bool {
+        unsafe { (*RCC::ptr()).cifr.read().cssf().bit_is_set() }
+    }
+
+    /// Clears HSE failure flag of Clock Security System.
+    ///
+    /// Must be called from NMI handler, as otherwise NMI is raised again.
+    pub fn clear_css_interrupt() {
+        // NOTE(unsafe) CICR is write-only register, writing 0 to other flags has no effect
+        unsafe { (*RCC::ptr()).cicr.write(|write| write.cssc().set_bit()) };
+    }
+}
+
+/// Internal clock sources calibration register
+///
+/// Calibration values are loaded from factory at startup, while trimming is
+/// added on top of them to compensate for variations of voltage and temperature.
+///
+/// See Reference manual Ch. 6.4.2
+pub struct ICSCR(());
+impl ICSCR {
+    /// Return a raw pointer to the ICSCR register
+    #[inline]
+    pub fn inner(&mut self) -> &rcc::ICSCR {
+        unsafe { &(*RCC::ptr()).icscr }
+    }
+
+    /// Returns factory calibration of MSI.
+    pub fn msi_calibration(&mut self) -> u8 {
+        self.inner().read().msical().bits()
+    }
+
+    /// Returns trimming of MSI.
+    pub fn msi_trim(&mut self) -> u8 {
+        self.inner().read().msitrim().bits()
+    }
+
+    /// Sets trimming of MSI, which is added to its calibration as two's complement.
+    ///
+    /// See Reference manual Ch. 6.2.3
+    pub fn set_msi_trim(&mut self, trim: u8) {
+        self.inner().modify(|_, write| unsafe { write.msitrim().bits(trim) });
+    }
+
+    /// Returns factory calibration of HSI16.
+    pub fn hsi16_calibration(&mut self) -> u8 {
+        self.inner().read().hsical().bits()
+    }
+
+    /// Returns trimming of HSI16.
+    pub fn hsi16_trim(&mut self) -> u8 {
+        self.inner().read().hsitrim().bits()
+    }
+
+    /// Sets trimming of HSI16 in range `0..=31`, where default value 16 keeps factory calibration.
+    ///
+    /// # Panics
+    ///
+    /// If `trim` is out of range.
+    ///
+    /// See Reference manual Ch. 6.2.2
+    pub fn set_hsi16_trim(&mut self, trim: u8) {
+        assert!(trim <= 31);
+        self.inner().modify(|_, write| unsafe { write.hsitrim().bits(trim) });
+    }
+}
+
 /// Backup domain control register.
 ///
 /// Note that it may be write protected and in order to modify it
@@ -174,6 +745,31 @@ impl BDCR {
         self.inner().modify(|_, write| write.rtcen().bit(is_on));
     }
 
+    /// Sets drive capability of LSE oscillator.
+    ///
+    /// Must be set while LSE is off, i.e. before `lse_enable(true)`, as once LSE is on
+    /// the drive can only be decreased to `LseDrive::Low`.
+    ///
+    /// See Reference manual Ch. 6.2.5
+    pub fn set_lse_drive(&mut self, drive: clocking::LseDrive) {
+        self.inner().modify(|_, write| unsafe { write.lsedrv().bits(drive.bits()) });
+    }
+
+    /// Selects low-speed clock to output on PC13 (LSCO).
+    ///
+    /// As BDCR and PC13 belong to the backup domain, write access to BDCR must be enabled,
+    /// which is the case until `freeze`, or later via `Power::remove_bdp`.
+    /// The selected clock must be enabled separately.
+    ///
+    /// See Reference manual Ch. 6.2.17
+    pub fn set_lsco(&mut self, src: clocking::LscoClkSource) {
+        self.inner().modify(|_, write| match src {
+            clocking::LscoClkSource::None => write.lscoen().clear_bit(),
+            clocking::LscoClkSource::LSI => write.lscosel().clear_bit().lscoen().set_bit(),
+            clocking::LscoClkSource::LSE => write.lscosel().set_bit().lscoen().set_bit(),
+        });
+    }
+
     /// Sets LSE on/off
     pub fn lse_enable(&mut self, is_on: bool) {
         let inner = self.inner();
@@ -217,11 +813,160 @@ impl CSR {
     }
 }
 
+/// Peripherals independent clock configuration register
+///
+/// See Reference manual Ch. 6.4.28
+pub struct CCIPR(());
+impl CCIPR {
+    /// Return a raw pointer to the CCIPR register
+    #[inline]
+    pub fn inner(&mut self) -> &rcc::CCIPR {
+        unsafe { &(*RCC::ptr()).ccipr }
+    }
+
+    /// Returns kernel clock of LPTIM1.
+    pub fn lptim1_clock(&mut self) -> clocking::LpTimClkSource {
+        match self.inner().read().lptim1sel().bits() {
+            0 => clocking::LpTimClkSource::PCLK,
+            1 => clocking::LpTimClkSource::LSI,
+            2 => clocking::LpTimClkSource::HSI16,
+            3 => clocking::LpTimClkSource::LSE,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Returns kernel clock of I2C1, which is needed to compute its timings.
+    ///
+    /// Use `I2cClkSource::freq` to get its frequency.
+    pub fn i2c1_clock(&mut self) -> clocking::I2cClkSource {
+        clocking::I2cClkSource::from_bits(self.inner().read().i2c1sel().bits())
+    }
+
+    /// Returns kernel clock of I2C2, which is needed to compute its timings.
+    pub fn i2c2_clock(&mut self) -> clocking::I2cClkSource {
+        clocking::I2cClkSource::from_bits(self.inner().read().i2c2sel().bits())
+    }
+
+    /// Returns kernel clock of I2C3, which is needed to compute its timings.
+    pub fn i2c3_clock(&mut self) -> clocking::I2cClkSource {
+        clocking::I2cClkSource::from_bits(self.inner().read().i2c3sel().bits())
+    }
+
+    /// Selects kernel clock of I2C1.
+    ///
+    /// HSI16 must be turned on separately, with `always_on` in order for I2C to operate in Stop mode,
+    /// see `CR::enable_hsi16`.
+    pub fn set_i2c1_clock(&mut self, clock: clocking::I2cClkSource) {
+        self.inner().modify(|_, write| unsafe { write.i2c1sel().bits(clock.bits()) });
+    }
+
+    /// Selects kernel clock of I2C2, see `set_i2c1_clock`.
+    pub fn set_i2c2_clock(&mut self, clock: clocking::I2cClkSource) {
+        self.inner().modify(|_, write| unsafe { write.i2c2sel().bits(clock.bits()) });
+    }
+
+    /// Selects kernel clock of I2C3, see `set_i2c1_clock`.
+    pub fn set_i2c3_clock(&mut self, clock: clocking::I2cClkSource) {
+        self.inner().modify(|_, write| unsafe { write.i2c3sel().bits(clock.bits()) });
+    }
+
+    /// Selects the 48 MHz clock of USB, RNG and SDMMC, turning the source on.
+    ///
+    /// Returns frequency of the clock, or `ClockError::InvalidFrequency` without changing
+    /// the selection, if the source does not provide 48 MHz (see `Clk48ClkSource::check`),
+    /// or it is MSI, which drives SYSCLK or PLL with another range.
+    ///
+    /// Note that `Clocks` are not updated, prefer `CFGR::clk48` to select clock before `freeze`.
+    pub fn set_clk48_source(&mut self, src: clocking::Clk48ClkSource) -> Result<Hertz, clocking::ClockError> {
+        let _freq = src.check()?;
+        let rcc = unsafe { &*RCC::ptr() };
+        let (freq, clk48sel_bits) = src.try_configure(rcc, None)?;
+        self.inner().modify(|_, write| unsafe { write.clk48sel().bits(clk48sel_bits) });
+        Ok(Hertz(freq))
+    }
+
+    /// Selects kernel clock of ADC, configuring PLLSAI1 if it is selected.
+    ///
+    /// Note that `Clocks` are not updated, prefer `CFGR::adcclk` to select clock before `freeze`.
+    pub fn set_adc_clock(&mut self, clock: clocking::AdcClkSource) {
+        if let clocking::AdcClkSource::PLLSAI1R(s) = clock {
+            let rcc = unsafe { &*RCC::ptr() };
+            let (_, _, freq) = s.configure(rcc);
+            assert!(freq.is_some(), "PLLSAI1 R output must be enabled to drive ADC");
+        }
+        self.inner().modify(|_, write| unsafe { write.adcsel().bits(clock.bits()) });
+    }
+
+    /// Selects kernel clock of SAI1, configuring PLLSAI1 if it is selected.
+    ///
+    /// Note that `Clocks` are not updated, prefer `CFGR::sai1clk` to select clock before `freeze`.
+    pub fn set_sai1_clock(&mut self, clock: clocking::Sai1ClkSource) {
+        if let clocking::Sai1ClkSource::PLLSAI1P(s) = clock {
+            let rcc = unsafe { &*RCC::ptr() };
+            let (freq, _, _) = s.configure(rcc);
+            assert!(freq.is_some(), "PLLSAI1 P output must be enabled to drive SAI1");
+        }
+        self.inner().modify(|_, write| unsafe { write.sai1sel().bits(clock.bits()) });
+    }
+
+    /// Selects kernel clock of LPTIM1.
+    ///
+    /// Selected clock must be enabled separately.
+    pub fn set_lptim1_clock(&mut self, clock: clocking::LpTimClkSource) {
+        self.inner().modify(|_, write| unsafe { write.lptim1sel().bits(clock.bits()) });
+    }
+}
+
+/// Microcontroller clock output (MCO) on PA8
+///
+/// Routes one of the internal clocks, divided by prescaler, to the pin for probing.
+///
+/// Frequency on the pin must stay within capability of the pin's output speed,
+/// at most 80 MHz with very high speed (see I/O AC characteristics in datasheet),
+/// so it is advised to configure the pin via `into_alt_fun_cfg` with the highest speed
+/// and use prescaler when clocks above few MHz are probed.
+///
+/// See Reference manual Ch. 6.2.16
+pub struct Mco {
+    pin: PA8<AF0>,
+}
+
+impl Mco {
+    /// Starts output of the `src` clock, divided by `psc`, on the `pin`.
+    pub fn new(pin: PA8<AF0>, src: clocking::McoClkSource, psc: clocking::McoPrescaler) -> Self {
+        let mut mco = Mco { pin };
+        mco.set_source(src, psc);
+        mco
+    }
+
+    /// Changes clock that is output on the pin.
+    pub fn set_source(&mut self, src: clocking::McoClkSource, psc: clocking::McoPrescaler) {
+        //MCOPRE occupies bits 28-30 of CFGR
+        const MCOPRE_OFFSET: u32 = 28;
+        const MCOPRE_MASK: u32 = 0b111 << MCOPRE_OFFSET;
+
+        // NOTE(unsafe) MCO fields of CFGR are owned by this instance
+        let cfgr = unsafe { &(*RCC::ptr()).cfgr };
+        cfgr.modify(|r, w| unsafe { w.bits((r.bits() & !MCOPRE_MASK) | ((psc.bits() as u32) << MCOPRE_OFFSET)).mcosel().bits(src.bits()) });
+    }
+
+    /// Disables clock output and releases the pin.
+    pub fn release(mut self) -> PA8<AF0> {
+        self.set_source(clocking::McoClkSource::None, clocking::McoPrescaler::Div1);
+        self.pin
+    }
+}
+
 /// Maximum value for System clock.
 ///
 /// Reference Ch. 6.2.8
 pub const SYS_CLOCK_MAX: u32 = 80_000_000;
 
+/// Maximum value for System clock in voltage Range 2.
+///
+/// Reference Ch. 5.1.7
+pub const SYS_CLOCK_MAX_RANGE2: u32 = 26_000_000;
+
 /// Clock configuration
 pub struct CFGR {
     /// AHB bus frequency
@@ -232,6 +977,12 @@ pub struct CFGR {
     pclk2: Option<u32>,
     /// SYSCLK - not Option because it cannot be None
     sysclk: clocking::SysClkSource,
+    /// CLK48 - 48 MHz domain of USB, RNG and SDMMC
+    clk48: Option<clocking::Clk48ClkSource>,
+    /// ADC kernel clock
+    adcclk: Option<clocking::AdcClkSource>,
+    /// SAI1 kernel clock
+    sai1clk: Option<clocking::Sai1ClkSource>,
 }
 
 impl CFGR {
@@ -259,9 +1010,41 @@ impl CFGR {
             if let clocking::PLLClkSource::None = s.src {
                 panic!("PLL must have input clock to drive SYSCLK");
             }
-        } else {
-            self.sysclk = src;
         }
+        self.sysclk = src;
+        self
+    }
+
+    /// Sets a source for the 48 MHz clock of USB, RNG and SDMMC.
+    ///
+    /// The source is turned on and selected by `freeze`.
+    /// RNG is not able to generate numbers without this clock, so driver of RNG
+    /// should refuse to start, when `Clocks::clk48` is `None`.
+    ///
+    /// # Panics
+    ///
+    /// If the source does not provide 48 MHz, see `Clk48ClkSource::check`.
+    pub fn clk48(mut self, src: clocking::Clk48ClkSource) -> Self {
+        if let Err(error) = src.check() {
+            panic!("CLK48 source of {} Hz is not valid: {:?}", src.freq(), error);
+        }
+        self.clk48 = Some(src);
+        self
+    }
+
+    /// Sets a source for the kernel clock of ADC.
+    ///
+    /// The source is selected by `freeze`.
+    pub fn adcclk(mut self, src: clocking::AdcClkSource) -> Self {
+        self.adcclk = Some(src);
+        self
+    }
+
+    /// Sets a source for the kernel clock of SAI1.
+    ///
+    /// The source is selected by `freeze`.
+    pub fn sai1clk(mut self, src: clocking::Sai1ClkSource) -> Self {
+        self.sai1clk = Some(src);
         self
     }
 
@@ -293,15 +1076,101 @@ impl CFGR {
         }
     }
 
+    #[inline]
+    fn hpre_div(hpre_bits: u8) -> u32 {
+        match hpre_bits {
+            0b1000 => 2,
+            0b1001 => 4,
+            0b1010 => 8,
+            0b1011 => 16,
+            0b1100 => 64,
+            0b1101 => 128,
+            0b1110 => 256,
+            0b1111 => 512,
+            _ => 1,
+        }
+    }
+
+    #[inline]
+    fn ppre_div(ppre_bits: u8) -> u8 {
+        match ppre_bits {
+            0b100 => 2,
+            0b101 => 4,
+            0b110 => 8,
+            0b111 => 16,
+            _ => 1,
+        }
+    }
+
+    #[inline]
+    fn check_sys_clock(sys_clock: u32, is_range1: bool) {
+        assert!(sys_clock <= SYS_CLOCK_MAX, "SYSCLK {} Hz exceeds maximum of {} Hz", sys_clock, SYS_CLOCK_MAX);
+        assert!(
+            is_range1 || sys_clock <= SYS_CLOCK_MAX_RANGE2,
+            "SYSCLK {} Hz requires voltage Range 1, see `Power::set_voltage_range`",
+            sys_clock
+        );
+    }
+
+    /// Selects voltage Range 1, if `sys_clock` requires it, and waits for regulator to be ready.
+    ///
+    /// Reference Ch. 5.1.8
+    fn boost_regulator(sys_clock: u32) {
+        if sys_clock > SYS_CLOCK_MAX_RANGE2 && VoltageRange::current() != VoltageRange::Range1 {
+            VoltageRange::Range1.select();
+        }
+        // Regulator may be still settling after reset or previous change of the range
+        while unsafe { (*PWR::ptr()).sr2.read().vosf().bit_is_set() } {}
+    }
+
     /// Freezes the clock configuration, making it effective
+    ///
+    /// Voltage Range 1 is selected, when SYSCLK exceeds `SYS_CLOCK_MAX_RANGE2`.
+    /// When flash needs wait states, its prefetch buffer is enabled.
+    /// Instruction cache is enabled, use `ACR::disable_instruction_cache` afterwards to opt out.
+    ///
+    /// # Panics
+    ///
+    /// If SYSCLK exceeds `SYS_CLOCK_MAX`, as flash latency would not be sufficient.
+    ///
+    /// Also if MSI auto-calibration is requested, but LSE fails to start.
     pub fn freeze(self, acr: &mut ACR) -> Clocks {
+        match self.freeze_with(acr, None) {
+            Ok(clocks) => clocks,
+            Err(error) => panic!("Failed to configure clocks: {:?}", error),
+        }
+    }
+
+    /// Same as `freeze`, but waits for each clock to become ready at most `retries` times.
+    ///
+    /// This is the recommended way to configure clocks, so that missing or broken crystal results in
+    /// `ClockError::Timeout` instead of hanging forever.
+    pub fn try_freeze(self, acr: &mut ACR, retries: u32) -> Result<Clocks, clocking::ClockError> {
+        self.freeze_with(acr, Some(retries))
+    }
+
+    fn freeze_with(self, acr: &mut ACR, retries: Option<u32>) -> Result<Clocks, clocking::ClockError> {
         let rcc = unsafe { &*RCC::ptr() };
 
+        // Regulator must be ready for the target frequency before PLL is engaged
+        Self::boost_regulator(self.sysclk.freq());
+        let is_range1 = VoltageRange::current() == VoltageRange::Range1;
+        Self::check_sys_clock(self.sysclk.freq(), is_range1);
+
+        // Flash latency is increased before switching to higher frequency and decreased after (see 3.3.3)
+        // Configuring the source may already speed up SYSCLK, e.g. MSI, which drives it after reset
+        let range = VoltageRange::current();
+        let latency = ACR::latency_for(Hertz(self.sysclk.freq()), range);
+        let is_faster = latency > acr.latency();
+        if is_faster {
+            let _latency = acr.set_latency_for(Hertz(self.sysclk.freq()), range);
+        }
+
         let (sys_clock, sw_bits) = match self.sysclk {
-            clocking::SysClkSource::MSI(s) => s.configure(rcc),
-            clocking::SysClkSource::HSI16(s) => s.configure(rcc),
-            clocking::SysClkSource::HSE(s) => s.configure(rcc),
-            clocking::SysClkSource::PLL(s) => s.configure(rcc),
+            clocking::SysClkSource::MSI(s) => s.try_configure(rcc, retries)?,
+            clocking::SysClkSource::HSI16(s) => s.try_configure(rcc, retries)?,
+            clocking::SysClkSource::HSE(s) => s.try_configure(rcc, retries)?,
+            clocking::SysClkSource::PLL(s) => s.try_configure(rcc, retries)?,
         };
 
         //Reference Ch. 6.4.3
@@ -313,30 +1182,55 @@ impl CFGR {
         let (ppre2_bits, ppre2) = Self::calc_apb(ahb, self.pclk2);
         let apb2 = ahb / ppre2 as u32;
 
-        // Reference AN4621 note Figure. 4
-        // from 0 wait state to 4
-        let latency = if sys_clock <= 16_000_000 {
-            0b000
-        } else if sys_clock <= 32_000_000 {
-            0b001
-        } else if sys_clock <= 48_000_00 {
-            0b010
-        } else if sys_clock <= 64_000_00 {
-            0b011
-        } else {
-            0b100
+        rcc.cfgr.modify(|_, w| unsafe { w.ppre2().bits(ppre2_bits).ppre1().bits(ppre1_bits).hpre().bits(hpre_bits).sw().bits(sw_bits) });
+        while rcc.cfgr.read().sws().bits() != sw_bits {}
+
+        if !is_faster {
+            let _latency = acr.set_latency_for(Hertz(sys_clock), range);
+        }
+        // Prefetch is of use only with wait states
+        if latency > 0 {
+            acr.enable_prefetch();
+        }
+        acr.enable_instruction_cache();
+
+        let clk48 = match self.clk48 {
+            Some(src) => {
+                let (freq, clk48sel_bits) = src.try_configure(rcc, retries)?;
+                rcc.ccipr.modify(|_, w| unsafe { w.clk48sel().bits(clk48sel_bits) });
+                Some(Hertz(freq))
+            },
+            None => None,
         };
 
-        acr.acr().write(|w| unsafe { w.latency().bits(latency) });
+        let adcclk = match self.adcclk {
+            Some(src) => {
+                if let clocking::AdcClkSource::PLLSAI1R(s) = src {
+                    let _c = s.try_configure(rcc, retries)?;
+                }
+                rcc.ccipr.modify(|_, w| unsafe { w.adcsel().bits(src.bits()) });
+                src.freq(sys_clock).map(Hertz)
+            },
+            None => None,
+        };
 
-        rcc.cfgr.modify(|_, w| unsafe { w.ppre2().bits(ppre2_bits).ppre1().bits(ppre1_bits).hpre().bits(hpre_bits).sw().bits(sw_bits) });
+        let sai1clk = match self.sai1clk {
+            Some(src) => {
+                if let clocking::Sai1ClkSource::PLLSAI1P(s) = src {
+                    let _c = s.try_configure(rcc, retries)?;
+                }
+                rcc.ccipr.modify(|_, w| unsafe { w.sai1sel().bits(src.bits()) });
+                src.freq().map(Hertz)
+            },
+            None => None,
+        };
 
         // Disable BDCR write access
         unsafe {
             (*PWR::ptr()).cr1.modify(|_, w| w.dbp().clear_bit());
         }
 
-        Clocks {
+        Ok(Clocks {
             hclk: Hertz(ahb),
             pclk1: Hertz(apb1),
             pclk2: Hertz(apb2),
@@ -349,9 +1243,17 @@ impl CFGR {
                 clocking::SysClkSource::PLL(s) => Some(s.m),
                 _ => None,
             },
+            pllq: match (self.sysclk, self.clk48) {
+                (_, Some(clocking::Clk48ClkSource::PLLQ(s))) | (clocking::SysClkSource::PLL(s), _) => s.freq_q().map(Hertz),
+                _ => None,
+            },
+            hpre: Self::hpre_div(hpre_bits) as u16,
             ppre1,
             ppre2,
-        }
+            clk48,
+            adcclk,
+            sai1clk,
+        })
     }
 }
 
@@ -372,10 +1274,22 @@ pub struct Clocks {
     pub pll_src: Option<clocking::PLLClkSource>,
     /// PLL clock source prescaler, "M" in the clock tree
     pub pll_psc: Option<u8>,
+    /// Frequency of PLL48M1CLK output (Q output of PLL), if it is enabled
+    pub pllq: Option<Hertz>,
+    /// AHB prescaler
+    pub hpre: u16,
     /// APB1 prescaler
     pub ppre1: u8,
     /// APB2 prescaler
     pub ppre2: u8,
+    /// Frequency of 48 MHz domain (CLK48), if its source is selected
+    ///
+    /// It is required by USB, RNG and SDMMC.
+    pub clk48: Option<Hertz>,
+    /// Frequency of ADC kernel clock, if its source is selected
+    pub adcclk: Option<Hertz>,
+    /// Frequency of SAI1 kernel clock, if its source is selected
+    pub sai1clk: Option<Hertz>,
 }
 
 impl Clocks {
@@ -394,6 +1308,11 @@ impl Clocks {
         self.pclk2
     }
 
+    /// Returns the value of the HCLK prescaler
+    pub fn hpre(&self) -> u16 {
+        self.hpre
+    }
+
     /// Returns the value of the PCLK1 prescaler
     pub fn ppre1(&self) -> u8 {
         self.ppre1
@@ -410,6 +1329,31 @@ impl Clocks {
     pub fn sysclk(&self) -> Hertz {
         self.sysclk
     }
+
+    /// Returns the frequency of the PLL48M1CLK output, if it is enabled
+    pub fn pllq(&self) -> Option<Hertz> {
+        self.pllq
+    }
+
+    /// Returns the frequency of the 48 MHz domain, if its source is selected
+    pub fn clk48(&self) -> Option<Hertz> {
+        self.clk48
+    }
+
+    /// Returns the frequency of ADC kernel clock, if its source is selected
+    pub fn adcclk(&self) -> Option<Hertz> {
+        self.adcclk
+    }
+
+    /// Returns the frequency of I2C kernel clock `src`, e.g. as returned by `CCIPR::i2c1_clock`
+    pub fn i2c_clock(&self, src: clocking::I2cClkSource) -> Hertz {
+        Hertz(src.freq(self.pclk1.0, self.sysclk.0))
+    }
+
+    /// Returns the frequency of SAI1 kernel clock, if its source is selected
+    pub fn sai1clk(&self) -> Option<Hertz> {
+        self.sai1clk
+    }
 }
 
 #[cfg(test)]
@@ -496,4 +1440,140 @@ mod tests {
         assert_eq!(ahb, sys_clock / 512);
 
     }
+
+    #[test]
+    pub fn pll_q_output() {
+        let hsi = clocking::HighSpeedInternal16RC { always_on: false, auto_start: false };
+        let pll = clocking::PLLClkOutput::new(clocking::PLLClkSource::HSI16(hsi), 2, 24, 4);
+        assert_eq!(pll.freq_q(), None);
+
+        let pll = pll.with_q(4);
+        assert_eq!(pll.freq_q(), Some(48_000_000));
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn pll_q_output_not_48mhz() {
+        let hsi = clocking::HighSpeedInternal16RC { always_on: false, auto_start: false };
+        let _ = clocking::PLLClkOutput::new(clocking::PLLClkSource::HSI16(hsi), 2, 20, 4).with_q(4);
+    }
+
+    #[test]
+    pub fn pll_sysclk_is_retained() {
+        let cfgr = CFGR {
+            hclk: None,
+            pclk1: None,
+            pclk2: None,
+            sysclk: clocking::SysClkSource::MSI(clocking::MediumSpeedInternalRC::new(4_000_000, false).unwrap()),
+            clk48: None,
+            adcclk: None,
+            sai1clk: None,
+        };
+
+        let hsi = clocking::HighSpeedInternal16RC { always_on: false, auto_start: false };
+        let pll = clocking::PLLClkOutput::new(clocking::PLLClkSource::HSI16(hsi), 2, 18, 2);
+        let cfgr = cfgr.sysclk(clocking::SysClkSource::PLL(pll));
+
+        match cfgr.sysclk {
+            clocking::SysClkSource::PLL(s) => assert_eq!(s.freq(), 72_000_000),
+            _ => panic!("PLL is not selected as SYSCLK"),
+        }
+    }
+
+    #[test]
+    pub fn prescaler_bits_to_div() {
+        let sys_clock = SYS_CLOCK_MAX;
+        for &div in [1, 2, 5, 6, 18, 40, 100, 300, 400].iter() {
+            let (hpre_bits, ahb) = CFGR::calc_ahb(sys_clock, Some(sys_clock / div));
+            assert_eq!(sys_clock / CFGR::hpre_div(hpre_bits), ahb);
+        }
+
+        for &div in [1, 2, 4, 8, 16].iter() {
+            let (ppre_bits, ppre) = CFGR::calc_apb(sys_clock, Some(sys_clock / div));
+            assert_eq!(CFGR::ppre_div(ppre_bits), ppre);
+        }
+    }
+
+    #[test]
+    pub fn check_sys_clock() {
+        CFGR::check_sys_clock(SYS_CLOCK_MAX, true);
+        CFGR::check_sys_clock(SYS_CLOCK_MAX_RANGE2, false);
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn check_sys_clock_overclock() {
+        CFGR::check_sys_clock(SYS_CLOCK_MAX + 1, true);
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn check_sys_clock_range2() {
+        CFGR::check_sys_clock(SYS_CLOCK_MAX_RANGE2 + 1, false);
+    }
+
+    #[test]
+    pub fn msi_range_freq() {
+        let ranges = [
+            100_000, 200_000, 400_000, 800_000, 1_000_000, 2_000_000, 4_000_000, 8_000_000, 16_000_000, 24_000_000, 32_000_000, 48_000_000,
+        ];
+
+        for &freq in ranges.iter() {
+            let msi = clocking::MediumSpeedInternalRC::new(freq, false).unwrap();
+            assert_eq!(clocking::MediumSpeedInternalRC::range_freq(msi.bits()), Some(freq));
+        }
+        assert_eq!(clocking::MediumSpeedInternalRC::range_freq(0b1100), None);
+        assert!(clocking::MediumSpeedInternalRC::new(3_000_000, false).is_err());
+    }
+
+    #[test]
+    pub fn check_clk48() {
+        assert_eq!(clocking::check_clk48(48_000_000), Ok(48_000_000));
+        assert_eq!(clocking::check_clk48(48_000_000 - clocking::CLK48_TOLERANCE), Ok(47_880_000));
+        assert_eq!(clocking::check_clk48(48_000_000 + clocking::CLK48_TOLERANCE), Ok(48_120_000));
+        assert_eq!(clocking::check_clk48(47_879_999), Err(clocking::ClockError::InvalidFrequency));
+        assert_eq!(clocking::check_clk48(48_120_001), Err(clocking::ClockError::InvalidFrequency));
+        assert_eq!(clocking::check_clk48(0), Err(clocking::ClockError::InvalidFrequency));
+
+        let hsi = clocking::HighSpeedInternal16RC { always_on: false, auto_start: false };
+        let pllsai1 = clocking::PllSai1::new(clocking::PLLClkSource::HSI16(hsi), 2, 24);
+        assert!(clocking::Clk48ClkSource::PLLSAI1Q(pllsai1).check().is_err());
+        assert!(clocking::Clk48ClkSource::PLLSAI1Q(pllsai1.with_q(4)).check().is_ok());
+        assert!(clocking::Clk48ClkSource::PLLSAI1Q(pllsai1.with_q(2)).check().is_err());
+        assert!(clocking::Clk48ClkSource::MSI(clocking::MediumSpeedInternalRC::new(48_000_000, true).unwrap()).check().is_ok());
+        assert!(clocking::Clk48ClkSource::MSI(clocking::MediumSpeedInternalRC::new(24_000_000, true).unwrap()).check().is_err());
+    }
+
+    #[test]
+    pub fn calculate_latency() {
+        let table = [
+            (16_000_000, 0b000),
+            (16_000_001, 0b001),
+            (32_000_000, 0b001),
+            (32_000_001, 0b010),
+            (48_000_000, 0b010),
+            (48_000_001, 0b011),
+            (64_000_000, 0b011),
+            (64_000_001, 0b100),
+            (SYS_CLOCK_MAX, 0b100),
+        ];
+
+        for &(sys_clock, latency) in table.iter() {
+            assert_eq!(ACR::latency_for(Hertz(sys_clock), VoltageRange::Range1), latency, "sys_clock = {}", sys_clock);
+        }
+
+        let table = [
+            (6_000_000, 0b000),
+            (6_000_001, 0b001),
+            (12_000_000, 0b001),
+            (12_000_001, 0b010),
+            (18_000_000, 0b010),
+            (18_000_001, 0b011),
+            (SYS_CLOCK_MAX_RANGE2, 0b011),
+        ];
+
+        for &(sys_clock, latency) in table.iter() {
+            assert_eq!(ACR::latency_for(Hertz(sys_clock), VoltageRange::Range2), latency, "sys_clock = {}", sys_clock);
+        }
+    }
 }
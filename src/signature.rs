@@ -0,0 +1,25 @@
+//! Device electronic signature
+//!
+//! Read-only values stored in system memory by manufacturer.
+//!
+//! See the chapter Device electronic signature of Reference manual
+
+use core::ptr;
+
+/// Address of 96-bit unique device ID
+pub const UID_BASE: usize = 0x1FFF_7590;
+/// Address of flash size in KB
+pub const FLASH_SIZE_BASE: usize = 0x1FFF_75E0;
+
+/// Returns 96-bit unique device ID, as words at `UID_BASE`, `UID_BASE + 4` and `UID_BASE + 8`.
+pub fn device_uid() -> [u32; 3] {
+    let uid = UID_BASE as *const u32;
+    // NOTE(unsafe) read-only location of system memory
+    unsafe { [ptr::read_volatile(uid), ptr::read_volatile(uid.add(1)), ptr::read_volatile(uid.add(2))] }
+}
+
+/// Returns size of flash in KB, as stored at `FLASH_SIZE_BASE`.
+pub fn flash_size_kb() -> u16 {
+    // NOTE(unsafe) read-only location of system memory
+    unsafe { ptr::read_volatile(FLASH_SIZE_BASE as *const u16) }
+}
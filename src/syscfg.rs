@@ -0,0 +1,92 @@
+//! System configuration controller
+
+use stm32l4x6::{syscfg, FLASH, SYSCFG};
+
+use crate::rcc::APB2;
+
+/// Constrained SYSCFG peripheral
+pub struct Syscfg(());
+impl Syscfg {
+    /// Enables SYSCFG clock on APB2 and takes ownership over SYSCFG.
+    pub fn new(_syscfg: SYSCFG, apb2: &mut APB2) -> Self {
+        apb2.enr().modify(|_, w| w.syscfgen().set_bit());
+
+        Syscfg(())
+    }
+
+    /// Access EXTICR1 register (EXTI lines 0-3)
+    pub fn exticr1(&mut self) -> &syscfg::EXTICR1 {
+        unsafe { &(*SYSCFG::ptr()).exticr1 }
+    }
+
+    /// Access EXTICR2 register (EXTI lines 4-7)
+    pub fn exticr2(&mut self) -> &syscfg::EXTICR2 {
+        unsafe { &(*SYSCFG::ptr()).exticr2 }
+    }
+
+    /// Access EXTICR3 register (EXTI lines 8-11)
+    pub fn exticr3(&mut self) -> &syscfg::EXTICR3 {
+        unsafe { &(*SYSCFG::ptr()).exticr3 }
+    }
+
+    /// Access EXTICR4 register (EXTI lines 12-15)
+    pub fn exticr4(&mut self) -> &syscfg::EXTICR4 {
+        unsafe { &(*SYSCFG::ptr()).exticr4 }
+    }
+
+    /// Returns whether parity check of SRAM2 is enabled.
+    ///
+    /// Parity check is enabled by SRAM2_PE option bit of flash.
+    /// On parity error, which is detected on read, NMI is raised.
+    pub fn is_sram2_parity_enabled(&mut self) -> bool {
+        // SRAM2_PE is active low
+        unsafe { (*FLASH::ptr()).optr.read().sram2_pe().bit_is_clear() }
+    }
+
+    /// Returns whether parity error of SRAM2 has been detected.
+    pub fn is_sram2_parity_error(&mut self) -> bool {
+        unsafe { (*SYSCFG::ptr()).cfgr2.read().spf().bit_is_set() }
+    }
+
+    /// Clears parity error flag of SRAM2.
+    pub fn clear_sram2_parity_error(&mut self) {
+        // NOTE(unsafe) Lock bits of CFGR2 are not affected by writing 0, while SPF is cleared by writing 1
+        unsafe { (*SYSCFG::ptr()).cfgr2.write(|w| w.spf().set_bit()) };
+    }
+
+    /// Connects parity error of SRAM2 to break input of TIM1, TIM8, TIM15, TIM16 and TIM17.
+    ///
+    /// It can be undone only by reset.
+    pub fn lock_sram2_parity_to_break(&mut self) {
+        unsafe { (*SYSCFG::ptr()).cfgr2.write(|w| w.spl().set_bit()) };
+    }
+
+    /// Erases content of SRAM2 and waits until it is done.
+    ///
+    /// Reference Ch. 9.2.4
+    pub fn erase_sram2(&mut self) {
+        let syscfg = unsafe { &(*SYSCFG::ptr()) };
+        // Erase is unlocked by write protection key
+        syscfg.skr.write(|w| unsafe { w.key().bits(0xCA) });
+        syscfg.skr.write(|w| unsafe { w.key().bits(0x53) });
+        syscfg.scsr.modify(|_, w| w.sram2er().set_bit());
+        while syscfg.scsr.read().sram2bsy().bit_is_set() {}
+    }
+
+    /// Selects GPIO port that drives EXTI `line`.
+    ///
+    /// `port` is index of port starting from 0 for GPIOA.
+    /// Reference Ch. 9.2.3
+    pub(crate) fn set_exti_port(&mut self, line: u8, port: u8) {
+        let offset = 4 * (line % 4);
+        let bits = |original: u32| (original & !(0b1111 << offset)) | ((port as u32) << offset);
+
+        match line / 4 {
+            0 => self.exticr1().modify(|r, w| unsafe { w.bits(bits(r.bits())) }),
+            1 => self.exticr2().modify(|r, w| unsafe { w.bits(bits(r.bits())) }),
+            2 => self.exticr3().modify(|r, w| unsafe { w.bits(bits(r.bits())) }),
+            3 => self.exticr4().modify(|r, w| unsafe { w.bits(bits(r.bits())) }),
+            _ => unreachable!(),
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! Features of advanced-control timers TIM1 and TIM8
+//!
+//! Reference Ch. 30 Advanced-control timers (TIM1/TIM8)
+use core::marker::PhantomData;
+
+use cast::u32;
+
+use stm32l4x6::{TIM1, TIM8};
+
+use super::{Timer, BKIN};
+
+//BIF flag of SR
+const BIF: u32 = 1 << 7;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+/// Active level of break input
+pub enum BreakPolarity {
+    /// Break is active on low level
+    Low,
+    /// Break is active on high level
+    High,
+}
+
+/// Break input of advanced timer.
+///
+/// When break becomes active, hardware clears MOE to put outputs into their safe state.
+pub struct Break<TIM, PIN> {
+    pin: PIN,
+    _tim: PhantomData<TIM>,
+}
+
+/// Calculates DTG value of BDTR for dead-time of `ticks` of timer's clock.
+///
+/// Dead-time is rounded down to the closest encodable value, which is 1008 ticks at most.
+/// Returns `None` if dead-time is 1024 ticks or more.
+///
+/// Reference Ch. 30.4.21 TIMx break and dead-time register
+fn calc_dtg(ticks: u32) -> Option<u8> {
+    match ticks {
+        0..=127 => Some(ticks as u8),
+        128..=255 => Some(0b1000_0000 | (ticks / 2 - 64) as u8),
+        256..=511 => Some(0b1100_0000 | (ticks / 8 - 32) as u8),
+        512..=1023 => Some(0b1110_0000 | (ticks / 16 - 32) as u8),
+        _ => None,
+    }
+}
+
+macro_rules! impl_advanced {
+    ($($TIMx:ident: $idx:expr),+) => {
+        $(
+            impl Timer<$TIMx> {
+                /// Sets dead-time, inserted between complementary outputs, in nanoseconds.
+                ///
+                /// Dead-time is rounded down to the closest value, that can be encoded with timer's clock.
+                ///
+                /// # Panics
+                ///
+                /// When dead-time is 1024 ticks of timer's clock or more, while up to 1023 ticks it is rounded down to 1008.
+                pub fn set_dead_time(&mut self, ns: u32) {
+                    let ticks = u64::from(ns) * u64::from(self.clock()) / 1_000_000_000;
+                    let dtg = u32(ticks).ok().and_then(calc_dtg).expect("Dead-time is out of range");
+
+                    self.tim.bdtr.modify(|_, w| unsafe { w.dtg().bits(dtg) });
+                }
+
+                /// Sets repetition counter, so that update event is generated once every `rcr + 1`
+                /// overflows of counter, or half-periods in center-aligned mode.
+                ///
+                /// New value takes effect at next update event.
+                pub fn set_repetition(&mut self, rcr: u8) {
+                    self.tim.rcr.write(|w| unsafe { w.rep().bits(rcr) });
+                }
+
+                /// Enables break input on `pin` with active level `polarity`.
+                ///
+                /// When `auto_output` is set (AOE), outputs are re-enabled automatically on next
+                /// update event once break is not active anymore.
+                /// Otherwise they must be re-enabled with `Break::resume_outputs`.
+                ///
+                /// # Panics
+                ///
+                /// In debug mode the function checks that `pin` belongs to the timer.
+                pub fn enable_break<PIN: BKIN>(&mut self, pin: PIN, polarity: BreakPolarity, auto_output: bool) -> Break<$TIMx, PIN> {
+                    debug_assert_eq!(PIN::TIM_IDX, $idx);
+
+                    self.tim.bdtr.modify(|_, w| w.bkp().bit(polarity == BreakPolarity::High)
+                                                 .aoe().bit(auto_output)
+                                                 .bke().set_bit());
+
+                    Break {
+                        pin,
+                        _tim: PhantomData
+                    }
+                }
+            }
+
+            impl<PIN: BKIN> Break<$TIMx, PIN> {
+                /// Returns whether break has occurred and its flag has not been cleared yet.
+                pub fn is_pending(&self) -> bool {
+                    unsafe { (*$TIMx::ptr()).sr.read().bif().bit_is_set() }
+                }
+
+                /// Clears break flag.
+                pub fn clear_interrupt(&mut self) {
+                    // NOTE(unsafe) SR flags are cleared by writing 0, writing 1 has no effect
+                    unsafe { (*$TIMx::ptr()).sr.write(|w| w.bits(!BIF)) };
+                }
+
+                /// Enables interrupt on break.
+                pub fn subscribe(&mut self) {
+                    unsafe { (*$TIMx::ptr()).dier.modify(|_, w| w.bie().set_bit()) };
+                }
+
+                /// Disables interrupt on break.
+                pub fn unsubscribe(&mut self) {
+                    unsafe { (*$TIMx::ptr()).dier.modify(|_, w| w.bie().clear_bit()) };
+                }
+
+                /// Re-enables outputs, disabled by break.
+                ///
+                /// Has no effect while break is still active.
+                pub fn resume_outputs(&mut self) {
+                    unsafe { (*$TIMx::ptr()).bdtr.modify(|_, w| w.moe().set_bit()) };
+                }
+
+                /// Disables break input and releases pin.
+                pub fn release(self) -> PIN {
+                    unsafe { (*$TIMx::ptr()).bdtr.modify(|_, w| w.bke().clear_bit()) };
+                    self.pin
+                }
+            }
+        )+
+    }
+}
+
+impl_advanced!(TIM1: 1, TIM8: 8);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn calculate_dtg() {
+        assert_eq!(calc_dtg(0), Some(0));
+        assert_eq!(calc_dtg(127), Some(127));
+        assert_eq!(calc_dtg(128), Some(0b1000_0000));
+        assert_eq!(calc_dtg(255), Some(0b1011_1111));
+        assert_eq!(calc_dtg(256), Some(0b1100_0000));
+        assert_eq!(calc_dtg(504), Some(0b1101_1111));
+        assert_eq!(calc_dtg(512), Some(0b1110_0000));
+        assert_eq!(calc_dtg(1008), Some(0b1111_1111));
+        assert_eq!(calc_dtg(1009), Some(0b1111_1111));
+        assert_eq!(calc_dtg(1023), Some(0b1111_1111));
+        assert_eq!(calc_dtg(1024), None);
+    }
+}
@@ -0,0 +1,215 @@
+//! Input capture
+//!
+//! On selected edge of input signal timer's channel latches current value of the counter into CCRx.
+//! Period of the signal can be measured as difference between two consecutive captures,
+//! given that timer's frequency is high enough to not overflow in-between.
+//!
+//! Reference Ch. 31.3.7 Input capture mode
+use core::marker::PhantomData;
+
+use nb;
+use void::Void;
+
+use stm32l4x6::{TIM2, TIM3, TIM4, TIM5};
+
+use super::{Channel, Pin, Timer};
+use crate::gpio::Edge;
+
+//CCxS: 01 maps ICx on TIx
+const CCS_TI: u32 = 0b01;
+const ICPSC_OFFSET: u8 = 2;
+const ICF_OFFSET: u8 = 4;
+const ICF_MAX: u8 = 0b1111;
+
+//All bits of channel within CCMRx
+const CCMR_MASK: u32 = 0xff | (1 << 16);
+
+//CCxP and CCxNP of CCER
+const CCP: u32 = 1 << 1;
+const CCNP: u32 = 1 << 3;
+const CCE: u32 = 1;
+
+//CCxOF flag of SR is located at bit 8 + x
+const CCOF_OFFSET: u8 = 8;
+
+#[derive(Copy, Clone)]
+#[repr(u8)]
+/// Number of events needed to perform capture
+pub enum Prescaler {
+    /// Capture on each event
+    Div1 = 0b00,
+    /// Capture once every 2 events
+    Div2 = 0b01,
+    /// Capture once every 4 events
+    Div4 = 0b10,
+    /// Capture once every 8 events
+    Div8 = 0b11,
+}
+
+#[derive(Copy, Clone)]
+/// Input capture configuration
+pub struct CaptureCfg {
+    /// Edge of input signal that triggers capture
+    pub edge: Edge,
+    /// Capture prescaler
+    pub prescaler: Prescaler,
+    /// Digital filter of input, ICxF value in range `0..=15`
+    ///
+    /// 0 disables filter.
+    pub filter: u8,
+}
+
+impl Default for CaptureCfg {
+    fn default() -> Self {
+        Self {
+            edge: Edge::Rising,
+            prescaler: Prescaler::Div1,
+            filter: 0,
+        }
+    }
+}
+
+/// Input capture on timer's channel.
+pub struct Capture<TIM, PIN> {
+    pin: PIN,
+    _tim: PhantomData<TIM>,
+}
+
+macro_rules! impl_capture {
+    ($($TIMx:ident: [idx: $idx:expr; channels: [$($CH:ident => {ccmr: $ccmr:ident; ccr: $ccr:ident},)+]])+) => {
+        $(
+            impl Timer<$TIMx> {
+                /// Configures channel of the `pin` to capture input and enables capture.
+                ///
+                /// # Panics
+                ///
+                /// When filter value is out of range.
+                ///
+                /// In debug mode the function checks that `pin` belongs to the timer.
+                pub fn capture<PIN: Pin>(&mut self, pin: PIN, cfg: CaptureCfg) -> Capture<$TIMx, PIN> {
+                    debug_assert_eq!(PIN::TIM_IDX, $idx);
+                    assert!(cfg.filter <= ICF_MAX);
+
+                    let ccer_offset = PIN::CHANNEL.ccer_offset();
+                    //Channel must be disabled while CCxS is written
+                    self.tim.ccer.modify(|r, w| unsafe { w.bits(r.bits() & !((CCE | CCP | CCNP) << ccer_offset)) });
+
+                    let offset = PIN::CHANNEL.ccmr_offset();
+                    let cfg_bits = CCS_TI | ((cfg.prescaler as u32) << ICPSC_OFFSET) | ((cfg.filter as u32) << ICF_OFFSET);
+                    #[allow(unreachable_patterns)]
+                    match PIN::CHANNEL {
+                        $(
+                            Channel::$CH => self.tim.$ccmr.modify(|r, w| unsafe {
+                                w.bits((r.bits() & !(CCMR_MASK << offset)) | (cfg_bits << offset))
+                            }),
+                        )+
+                        _ => unreachable!(),
+                    }
+
+                    let polarity = match cfg.edge {
+                        Edge::Rising => 0,
+                        Edge::Falling => CCP,
+                        Edge::RisingFalling => CCP | CCNP,
+                    };
+                    self.tim.ccer.modify(|r, w| unsafe { w.bits(r.bits() | ((polarity | CCE) << ccer_offset)) });
+
+                    Capture {
+                        pin,
+                        _tim: PhantomData
+                    }
+                }
+            }
+
+            impl<PIN: Pin> Capture<$TIMx, PIN> {
+                /// Reads captured value, if capture happened since last read.
+                pub fn read(&mut self) -> nb::Result<u32, Void> {
+                    let tim = unsafe { &(*$TIMx::ptr()) };
+                    let flag = 1 << (PIN::CHANNEL as u8);
+
+                    match tim.sr.read().bits() & flag {
+                        0 => Err(nb::Error::WouldBlock),
+                        // Reading CCRx clears CCxIF
+                        #[allow(unreachable_patterns)]
+                        _ => match PIN::CHANNEL {
+                            $(
+                                Channel::$CH => Ok(tim.$ccr.read().bits()),
+                            )+
+                            _ => unreachable!(),
+                        }
+                    }
+                }
+
+                /// Returns whether capture happened while previous value has not been read yet.
+                pub fn is_overcaptured(&self) -> bool {
+                    let flag = 1 << (CCOF_OFFSET + PIN::CHANNEL as u8);
+                    unsafe { (*$TIMx::ptr()).sr.read().bits() & flag != 0 }
+                }
+
+                /// Clears overcapture flag.
+                pub fn clear_overcapture(&mut self) {
+                    let flag = 1 << (CCOF_OFFSET + PIN::CHANNEL as u8);
+                    // NOTE(unsafe) SR flags are cleared by writing 0, writing 1 has no effect
+                    unsafe { (*$TIMx::ptr()).sr.write(|w| w.bits(!flag)) };
+                }
+
+                /// Enables interrupt on capture.
+                pub fn subscribe(&mut self) {
+                    let flag = 1 << (PIN::CHANNEL as u8);
+                    unsafe { (*$TIMx::ptr()).dier.modify(|r, w| w.bits(r.bits() | flag)) };
+                }
+
+                /// Disables interrupt on capture.
+                pub fn unsubscribe(&mut self) {
+                    let flag = 1 << (PIN::CHANNEL as u8);
+                    unsafe { (*$TIMx::ptr()).dier.modify(|r, w| w.bits(r.bits() & !flag)) };
+                }
+
+                /// Disables capture and releases pin.
+                pub fn release(self) -> PIN {
+                    let offset = PIN::CHANNEL.ccer_offset();
+                    unsafe { (*$TIMx::ptr()).ccer.modify(|r, w| w.bits(r.bits() & !(CCE << offset))) };
+                    self.pin
+                }
+            }
+        )+
+    }
+}
+
+impl_capture!(
+    TIM2: [
+        idx: 2;
+        channels: [
+            CH1 => {ccmr: ccmr1_output; ccr: ccr1},
+            CH2 => {ccmr: ccmr1_output; ccr: ccr2},
+            CH3 => {ccmr: ccmr2_output; ccr: ccr3},
+            CH4 => {ccmr: ccmr2_output; ccr: ccr4},
+        ]
+    ]
+    TIM3: [
+        idx: 3;
+        channels: [
+            CH1 => {ccmr: ccmr1_output; ccr: ccr1},
+            CH2 => {ccmr: ccmr1_output; ccr: ccr2},
+            CH3 => {ccmr: ccmr2_output; ccr: ccr3},
+            CH4 => {ccmr: ccmr2_output; ccr: ccr4},
+        ]
+    ]
+    TIM4: [
+        idx: 4;
+        channels: [
+            CH1 => {ccmr: ccmr1_output; ccr: ccr1},
+            CH2 => {ccmr: ccmr1_output; ccr: ccr2},
+            CH3 => {ccmr: ccmr2_output; ccr: ccr3},
+            CH4 => {ccmr: ccmr2_output; ccr: ccr4},
+        ]
+    ]
+    TIM5: [
+        idx: 5;
+        channels: [
+            CH1 => {ccmr: ccmr1_output; ccr: ccr1},
+            CH2 => {ccmr: ccmr1_output; ccr: ccr2},
+            CH3 => {ccmr: ccmr2_output; ccr: ccr3},
+            CH4 => {ccmr: ccmr2_output; ccr: ccr4},
+        ]
+    ]
+);
@@ -0,0 +1,195 @@
+//! Output compare
+//!
+//! When counter matches value of CCRx, timer's channel performs configured action on its output.
+//! Unlike PWM it is suitable for generation of single events at precise time.
+//!
+//! Reference Ch. 31.3.9 Output compare mode
+use core::marker::PhantomData;
+
+use stm32l4x6::{TIM2, TIM3, TIM4, TIM5, TIM15, TIM16, TIM17};
+
+use super::{Channel, Pin, Timer};
+
+//OCxM of CCMRx occupies bits 4-6 and bit 16
+const OCM_OFFSET: u8 = 4;
+//All bits of channel within CCMRx
+const CCMR_MASK: u32 = 0xff | (1 << 16);
+const CCE: u32 = 1;
+
+#[derive(Copy, Clone)]
+#[repr(u8)]
+/// Action performed on channel's output when counter matches CCRx
+pub enum CompareMode {
+    /// Output is not affected
+    Frozen = 0b000,
+    /// Output becomes active on match
+    Active = 0b001,
+    /// Output becomes inactive on match
+    Inactive = 0b010,
+    /// Output toggles on match
+    Toggle = 0b011,
+    /// Output is forced to inactive level
+    ForceInactive = 0b100,
+    /// Output is forced to active level
+    ForceActive = 0b101,
+}
+
+/// Output compare on timer's channel.
+pub struct Compare<TIM, PIN> {
+    pin: PIN,
+    _tim: PhantomData<TIM>,
+}
+
+macro_rules! impl_compare {
+    ($($TIMx:ident: [idx: $idx:expr; $(bdtr: $bdtr:ident;)? channels: [$($CH:ident => {ccmr: $ccmr:ident; ccr: $ccr:ident},)+]])+) => {
+        $(
+            impl Timer<$TIMx> {
+                /// Configures channel of the `pin` to output compare with `mode`.
+                ///
+                /// Output is disabled until `enable` is called.
+                ///
+                /// # Panics
+                ///
+                /// In debug mode the function checks that `pin` belongs to the timer.
+                pub fn compare<PIN: Pin>(&mut self, pin: PIN, mode: CompareMode) -> Compare<$TIMx, PIN> {
+                    debug_assert_eq!(PIN::TIM_IDX, $idx);
+
+                    let mut compare: Compare<$TIMx, PIN> = Compare {
+                        pin,
+                        _tim: PhantomData
+                    };
+                    compare.disable();
+                    compare.set_mode(mode);
+
+                    $(
+                        //Outputs of timers with break feature are gated by MOE
+                        self.tim.$bdtr.modify(|_, w| w.moe().set_bit());
+                    )?
+
+                    compare
+                }
+            }
+
+            impl<PIN: Pin> Compare<$TIMx, PIN> {
+                /// Sets action performed on match.
+                pub fn set_mode(&mut self, mode: CompareMode) {
+                    let tim = unsafe { &(*$TIMx::ptr()) };
+                    let offset = PIN::CHANNEL.ccmr_offset();
+                    // NOTE(unsafe) Channel's bits of CCMRx are owned by this instance
+                    #[allow(unreachable_patterns)]
+                    match PIN::CHANNEL {
+                        $(
+                            Channel::$CH => tim.$ccmr.modify(|r, w| unsafe {
+                                w.bits((r.bits() & !(CCMR_MASK << offset)) | (((mode as u32) << OCM_OFFSET) << offset))
+                            }),
+                        )+
+                        _ => unreachable!(),
+                    }
+                }
+
+                /// Sets value of counter at which action is performed.
+                pub fn set_compare(&mut self, value: u32) {
+                    let tim = unsafe { &(*$TIMx::ptr()) };
+                    // NOTE(unsafe) CCRx of channel is owned by this instance
+                    #[allow(unreachable_patterns)]
+                    match PIN::CHANNEL {
+                        $(
+                            Channel::$CH => tim.$ccr.write(|w| unsafe { w.bits(value) }),
+                        )+
+                        _ => unreachable!(),
+                    }
+                }
+
+                /// Retrieves value of counter at which action is performed.
+                pub fn get_compare(&self) -> u32 {
+                    let tim = unsafe { &(*$TIMx::ptr()) };
+                    #[allow(unreachable_patterns)]
+                    match PIN::CHANNEL {
+                        $(
+                            Channel::$CH => tim.$ccr.read().bits(),
+                        )+
+                        _ => unreachable!(),
+                    }
+                }
+
+                /// Enables channel's output.
+                pub fn enable(&mut self) {
+                    let offset = PIN::CHANNEL.ccer_offset();
+                    unsafe { (*$TIMx::ptr()).ccer.modify(|r, w| w.bits(r.bits() | (CCE << offset))) };
+                }
+
+                /// Disables channel's output.
+                pub fn disable(&mut self) {
+                    let offset = PIN::CHANNEL.ccer_offset();
+                    unsafe { (*$TIMx::ptr()).ccer.modify(|r, w| w.bits(r.bits() & !(CCE << offset))) };
+                }
+
+                /// Disables channel's output and releases its pin.
+                pub fn release(mut self) -> PIN {
+                    self.disable();
+                    self.pin
+                }
+            }
+        )+
+    }
+}
+
+impl_compare!(
+    TIM2: [
+        idx: 2;
+        channels: [
+            CH1 => {ccmr: ccmr1_output; ccr: ccr1},
+            CH2 => {ccmr: ccmr1_output; ccr: ccr2},
+            CH3 => {ccmr: ccmr2_output; ccr: ccr3},
+            CH4 => {ccmr: ccmr2_output; ccr: ccr4},
+        ]
+    ]
+    TIM3: [
+        idx: 3;
+        channels: [
+            CH1 => {ccmr: ccmr1_output; ccr: ccr1},
+            CH2 => {ccmr: ccmr1_output; ccr: ccr2},
+            CH3 => {ccmr: ccmr2_output; ccr: ccr3},
+            CH4 => {ccmr: ccmr2_output; ccr: ccr4},
+        ]
+    ]
+    TIM4: [
+        idx: 4;
+        channels: [
+            CH1 => {ccmr: ccmr1_output; ccr: ccr1},
+            CH2 => {ccmr: ccmr1_output; ccr: ccr2},
+            CH3 => {ccmr: ccmr2_output; ccr: ccr3},
+            CH4 => {ccmr: ccmr2_output; ccr: ccr4},
+        ]
+    ]
+    TIM5: [
+        idx: 5;
+        channels: [
+            CH1 => {ccmr: ccmr1_output; ccr: ccr1},
+            CH2 => {ccmr: ccmr1_output; ccr: ccr2},
+            CH3 => {ccmr: ccmr2_output; ccr: ccr3},
+            CH4 => {ccmr: ccmr2_output; ccr: ccr4},
+        ]
+    ]
+    TIM15: [
+        idx: 15;
+        bdtr: bdtr;
+        channels: [
+            CH1 => {ccmr: ccmr1_output; ccr: ccr1},
+        ]
+    ]
+    TIM16: [
+        idx: 16;
+        bdtr: bdtr;
+        channels: [
+            CH1 => {ccmr: ccmr1_output; ccr: ccr1},
+        ]
+    ]
+    TIM17: [
+        idx: 17;
+        bdtr: bdtr;
+        channels: [
+            CH1 => {ccmr: ccmr1_output; ccr: ccr1},
+        ]
+    ]
+);
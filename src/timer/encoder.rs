@@ -0,0 +1,130 @@
+//! Quadrature encoder interface
+//!
+//! Timer counts edges of two signals from incremental encoder, which are connected to its channels 1 and 2.
+//! Counter is incremented or decremented depending on sequence of transitions, without involving CPU.
+//!
+//! Reference Ch. 31.3.21 Encoder interface mode
+use stm32l4x6::{TIM2, TIM3, TIM4, TIM5};
+
+use super::{Channel, Direction, Pin};
+use crate::rcc::APB1;
+
+//CCxS: 01 maps ICx on TIx, for both CH1 and CH2
+const CCMR1_TI: u32 = 0b01 | (0b01 << 8);
+//CCxE, CCxP and CCxNP of CH1 and CH2
+const CCER_MASK: u32 = 0b1011 | (0b1011 << 4);
+
+#[derive(Copy, Clone)]
+#[repr(u8)]
+/// Edges counted by encoder
+pub enum EncoderMode {
+    /// Encoder mode 1, counts edges of TI2 depending on level of TI1
+    TI2 = 0b001,
+    /// Encoder mode 2, counts edges of TI1 depending on level of TI2
+    TI1 = 0b010,
+    /// Encoder mode 3, counts edges of both TI1 and TI2
+    Both = 0b011,
+}
+
+/// Quadrature encoder on timer's channels 1 and 2.
+pub struct Encoder<TIM, A, B> {
+    tim: TIM,
+    pins: (A, B),
+}
+
+macro_rules! impl_encoder {
+    ($($TIMx:ident: [idx: $idx:expr; max: $max:expr; constructor: $timx:ident; $APB:ident: {$enr:ident: $enr_bit:ident; $rstr:ident: $rstr_bit:ident}])+) => {
+        $(
+            impl<A: Pin, B: Pin> Encoder<$TIMx, A, B> {
+                /// Creates new encoder interface and starts counting.
+                ///
+                /// Pins must belong to channels 1 and 2 of the timer.
+                ///
+                /// # Panics
+                ///
+                /// In debug mode the function checks that pins correspond to the timer's channels.
+                pub fn $timx(tim: $TIMx, pins: (A, B), mode: EncoderMode, apb: &mut $APB) -> Self {
+                    debug_assert_eq!(A::TIM_IDX, $idx);
+                    debug_assert_eq!(B::TIM_IDX, $idx);
+                    debug_assert_eq!(A::CHANNEL, Channel::CH1);
+                    debug_assert_eq!(B::CHANNEL, Channel::CH2);
+
+                    // enable and reset peripheral to a clean slate state
+                    apb.$enr().modify(|_, w| w.$enr_bit().set_bit());
+                    apb.$rstr().modify(|_, w| w.$rstr_bit().set_bit());
+                    apb.$rstr().modify(|_, w| w.$rstr_bit().clear_bit());
+
+                    tim.ccmr1_output.write(|w| unsafe { w.bits(CCMR1_TI) });
+                    //Non-inverted polarity of both inputs
+                    tim.ccer.modify(|r, w| unsafe { w.bits(r.bits() & !CCER_MASK) });
+                    tim.smcr.modify(|_, w| unsafe { w.sms().bits(mode as u8) });
+                    tim.arr.write(|w| unsafe { w.bits($max) });
+                    tim.cr1.modify(|_, w| w.cen().set_bit());
+
+                    Self {
+                        tim,
+                        pins
+                    }
+                }
+
+                /// Retrieves current value of counter.
+                pub fn count(&self) -> u32 {
+                    self.tim.cnt.read().bits()
+                }
+
+                /// Retrieves direction in which encoder is rotated.
+                pub fn direction(&self) -> Direction {
+                    match self.tim.cr1.read().dir().bit_is_set() {
+                        true => Direction::Down,
+                        false => Direction::Up,
+                    }
+                }
+
+                /// Stops counting and releases the TIM peripheral with pins.
+                pub fn free(self) -> ($TIMx, (A, B)) {
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    (self.tim, self.pins)
+                }
+            }
+        )+
+    }
+}
+
+impl_encoder!(
+    TIM2: [
+        idx: 2;
+        max: 0xFFFF_FFFF;
+        constructor: tim2;
+        APB1: {
+            enr1: tim2en;
+            rstr1: tim2rst
+        }
+    ]
+    TIM3: [
+        idx: 3;
+        max: 0xFFFF;
+        constructor: tim3;
+        APB1: {
+            enr1: tim3en;
+            rstr1: tim3rst
+        }
+    ]
+    TIM4: [
+        idx: 4;
+        max: 0xFFFF;
+        constructor: tim4;
+        APB1: {
+            enr1: tim4en;
+            rstr1: tim4rst
+        }
+    ]
+    TIM5: [
+        idx: 5;
+        max: 0xFFFF_FFFF;
+        constructor: tim5;
+        APB1: {
+            enr1: tim5en;
+            rstr1: tim5rst
+        }
+    ]
+);
@@ -0,0 +1,150 @@
+//! External clock mode
+//!
+//! Counter is clocked by edges of signal on external trigger (ETR) pin instead of internal clock,
+//! which turns timer into hardware pulse counter.
+//!
+//! Reference Ch. 31.3.3 Clock selection, External clock source mode 2
+use stm32l4x6::{TIM1, TIM2, TIM3, TIM4, TIM8};
+
+use super::capture::Prescaler;
+use super::ETR;
+use crate::rcc::{APB1, APB2};
+
+const ETF_MAX: u8 = 0b1111;
+
+#[derive(Copy, Clone)]
+/// External trigger configuration
+pub struct EtrCfg {
+    /// Counts falling edges instead of rising
+    pub inverted: bool,
+    /// Prescaler of external trigger
+    ///
+    /// Frequency of prescaled signal must be at most 1/4 of timer's clock.
+    pub prescaler: Prescaler,
+    /// Digital filter of external trigger, ETF value in range `0..=15`
+    ///
+    /// 0 disables filter.
+    pub filter: u8,
+}
+
+impl Default for EtrCfg {
+    fn default() -> Self {
+        Self {
+            inverted: false,
+            prescaler: Prescaler::Div1,
+            filter: 0,
+        }
+    }
+}
+
+/// Timer counting pulses on its ETR pin.
+pub struct PulseCounter<TIM, PIN> {
+    tim: TIM,
+    pin: PIN,
+}
+
+macro_rules! impl_pulse_counter {
+    ($($TIMx:ident: [idx: $idx:expr; max: $max:expr; constructor: $timx:ident; $APB:ident: {$enr:ident: $enr_bit:ident; $rstr:ident: $rstr_bit:ident}])+) => {
+        $(
+            impl<PIN: ETR> PulseCounter<$TIMx, PIN> {
+                /// Creates new pulse counter and starts counting.
+                ///
+                /// # Panics
+                ///
+                /// When filter value is out of range.
+                ///
+                /// In debug mode the function checks that `pin` belongs to the timer.
+                pub fn $timx(tim: $TIMx, pin: PIN, cfg: EtrCfg, apb: &mut $APB) -> Self {
+                    debug_assert_eq!(PIN::TIM_IDX, $idx);
+                    assert!(cfg.filter <= ETF_MAX);
+
+                    // enable and reset peripheral to a clean slate state
+                    apb.$enr().modify(|_, w| w.$enr_bit().set_bit());
+                    apb.$rstr().modify(|_, w| w.$rstr_bit().set_bit());
+                    apb.$rstr().modify(|_, w| w.$rstr_bit().clear_bit());
+
+                    tim.smcr.modify(|_, w| unsafe {
+                        w.etp().bit(cfg.inverted)
+                         .etps().bits(cfg.prescaler as u8)
+                         .etf().bits(cfg.filter)
+                         .ece().set_bit()
+                    });
+                    tim.arr.write(|w| unsafe { w.bits($max) });
+                    tim.cr1.modify(|_, w| w.cen().set_bit());
+
+                    Self {
+                        tim,
+                        pin
+                    }
+                }
+
+                /// Retrieves number of pulses counted so far.
+                ///
+                /// Counter wraps around after reaching maximum value.
+                pub fn count(&self) -> u32 {
+                    self.tim.cnt.read().bits() & $max
+                }
+
+                /// Resets number of counted pulses.
+                pub fn reset(&mut self) {
+                    self.tim.cnt.reset();
+                }
+
+                /// Stops counting and releases the TIM peripheral with pin.
+                pub fn free(self) -> ($TIMx, PIN) {
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    self.tim.smcr.modify(|_, w| w.ece().clear_bit());
+                    (self.tim, self.pin)
+                }
+            }
+        )+
+    }
+}
+
+impl_pulse_counter!(
+    TIM1: [
+        idx: 1;
+        max: 0xFFFF;
+        constructor: tim1;
+        APB2: {
+            enr: tim1en;
+            rstr: tim1rst
+        }
+    ]
+    TIM8: [
+        idx: 8;
+        max: 0xFFFF;
+        constructor: tim8;
+        APB2: {
+            enr: tim8en;
+            rstr: tim8rst
+        }
+    ]
+    TIM2: [
+        idx: 2;
+        max: 0xFFFF_FFFF;
+        constructor: tim2;
+        APB1: {
+            enr1: tim2en;
+            rstr1: tim2rst
+        }
+    ]
+    TIM3: [
+        idx: 3;
+        max: 0xFFFF;
+        constructor: tim3;
+        APB1: {
+            enr1: tim3en;
+            rstr1: tim3rst
+        }
+    ]
+    TIM4: [
+        idx: 4;
+        max: 0xFFFF;
+        constructor: tim4;
+        APB1: {
+            enr1: tim4en;
+            rstr1: tim4rst
+        }
+    ]
+);
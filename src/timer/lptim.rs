@@ -0,0 +1,168 @@
+//! Low-power timer
+//!
+//! Unlike TIMx timers, LPTIM1 is clocked by its own kernel clock, selected through CCIPR's LPTIM1SEL.
+//! When driven by LSE, LSI or HSI16 it keeps running in Stop modes.
+//!
+//! Reference Ch. 32 Low-power timer (LPTIM)
+use cast::u16;
+use embedded_hal::timer::{CountDown, Periodic};
+use nb;
+use void::Void;
+
+use stm32l4x6::LPTIM1;
+
+use super::Event;
+use crate::rcc::clocking::LpTimClkSource;
+use crate::rcc::{APB1, CCIPR, Clocks};
+use crate::time::Hertz;
+
+//Maximum value of PRESC, which divides clock by 2^PRESC
+const PRESC_MAX: u8 = 7;
+
+/// Low-power timer
+pub struct LpTimer {
+    /// Frequency of kernel clock
+    clock: u32,
+    tim: LPTIM1,
+}
+
+impl LpTimer {
+    /// Creates new instance of LPTIM1, clocked by `source`.
+    ///
+    /// Selected clock must be enabled beforehand, e.g. LSE through `BDCR::lse_enable`
+    /// or LSI through `CSR::lsi_enable`.
+    pub fn lptim1<T: Into<Hertz>>(tim: LPTIM1, timeout: T, source: LpTimClkSource, clocks: Clocks, ccipr: &mut CCIPR, apb: &mut APB1) -> Self {
+        ccipr.set_lptim1_clock(source);
+
+        // enable and reset peripheral to a clean slate state
+        apb.enr1().modify(|_, w| w.lptim1en().set_bit());
+        apb.rstr1().modify(|_, w| w.lptim1rst().set_bit());
+        apb.rstr1().modify(|_, w| w.lptim1rst().clear_bit());
+
+        let mut timer = LpTimer {
+            clock: source.freq(clocks.pclk1.0),
+            tim,
+        };
+        timer.start(timeout);
+
+        timer
+    }
+
+    /// Starts listening for an `event`
+    ///
+    /// # Panics
+    ///
+    /// On capture/compare events, as LPTIM has no channels.
+    pub fn subscribe(&mut self, event: Event) {
+        match event {
+            Event::Timeout => self.set_interrupt(true),
+            Event::Capture(_) | Event::Compare(_) => panic!("LPTIM has no channels"),
+        }
+    }
+
+    /// Stops listening for an `event`
+    ///
+    /// # Panics
+    ///
+    /// On capture/compare events, as LPTIM has no channels.
+    pub fn unsubscribe(&mut self, event: Event) {
+        match event {
+            Event::Timeout => self.set_interrupt(false),
+            Event::Capture(_) | Event::Compare(_) => panic!("LPTIM has no channels"),
+        }
+    }
+
+    /// Returns whether `event` has occurred and its flag has not been cleared yet.
+    ///
+    /// # Panics
+    ///
+    /// On capture/compare events, as LPTIM has no channels.
+    pub fn is_pending(&self, event: Event) -> bool {
+        match event {
+            Event::Timeout => self.tim.isr.read().arrm().bit_is_set(),
+            Event::Capture(_) | Event::Compare(_) => panic!("LPTIM has no channels"),
+        }
+    }
+
+    /// Clears flag of `event`.
+    ///
+    /// # Panics
+    ///
+    /// On capture/compare events, as LPTIM has no channels.
+    pub fn clear_interrupt(&mut self, event: Event) {
+        match event {
+            Event::Timeout => self.reset_overflow(),
+            Event::Capture(_) | Event::Compare(_) => panic!("LPTIM has no channels"),
+        }
+    }
+
+    #[inline(always)]
+    /// Clears ARRM flag to clear status of overflow.
+    pub fn reset_overflow(&mut self) {
+        self.tim.icr.write(|w| w.arrmcf().set_bit());
+    }
+
+    /// Paused timer and releases the LPTIM peripheral
+    pub fn free(self) -> LPTIM1 {
+        self.tim.cr.modify(|_, w| w.enable().clear_bit());
+        self.tim
+    }
+
+    /// IER can be modified only while timer is disabled, hence it is restarted.
+    fn set_interrupt(&mut self, is_on: bool) {
+        let arr = self.tim.arr.read().arr().bits();
+
+        self.tim.cr.modify(|_, w| w.enable().clear_bit());
+        self.tim.ier.modify(|_, w| w.arrmie().bit(is_on));
+        self.enable(arr);
+    }
+
+    /// Enables timer and starts continuous counting up to `arr`.
+    fn enable(&mut self, arr: u16) {
+        self.tim.cr.modify(|_, w| w.enable().set_bit());
+
+        // ARR can be written only while timer is enabled
+        self.tim.arr.write(|w| unsafe { w.arr().bits(arr) });
+        while self.tim.isr.read().arrok().bit_is_clear() {}
+        self.tim.icr.write(|w| w.arrokcf().set_bit().arrmcf().set_bit());
+
+        self.tim.cr.modify(|_, w| w.cntstrt().set_bit());
+    }
+}
+
+impl Periodic for LpTimer {}
+impl CountDown for LpTimer {
+    type Time = Hertz;
+
+    /// Starts counting down with frequency of `timeout`.
+    ///
+    /// # Panics
+    ///
+    /// If `timeout` is longer than 0xFFFF periods of kernel clock divided by 128,
+    /// e.g. above 0.5 s with HSI16 or 256 s with LSE, or is shorter than single period of kernel clock.
+    fn start<T: Into<Self::Time>>(&mut self, timeout: T) {
+        let ticks = self.clock / timeout.into().0;
+
+        let mut presc = 0;
+        while presc < PRESC_MAX && (ticks >> presc) > 0xFFFF {
+            presc += 1;
+        }
+        let arr = u16(ticks >> presc).expect("Timeout is too long for LPTIM kernel clock");
+        assert!(arr > 0, "Timeout is too short for LPTIM kernel clock");
+
+        // CFGR can be written only while timer is disabled
+        self.tim.cr.modify(|_, w| w.enable().clear_bit());
+        self.tim.cfgr.modify(|_, w| unsafe { w.presc().bits(presc) });
+        self.enable(arr);
+    }
+
+    fn wait(&mut self) -> nb::Result<(), Void> {
+        match self.tim.isr.read().arrm().bit_is_clear() {
+            true => Err(nb::Error::WouldBlock),
+            false => {
+                self.reset_overflow();
+                Ok(())
+            }
+        }
+    }
+}
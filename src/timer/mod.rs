@@ -0,0 +1,1090 @@
+//! Hardware Timers
+//!
+//! # DMA requests
+//!
+//! STM32L4x6 has no DMAMUX, instead each DMA request is wired to fixed DMA channel and
+//! must be selected through CSELR of DMA (Reference Ch. 11.6.7).
+//!
+//! Requests on update event:
+//!
+//! | Timer | DMA1              | DMA2              |
+//! |-------|-------------------|-------------------|
+//! | TIM1  | channel 6, req 7  |                   |
+//! | TIM2  | channel 2, req 4  |                   |
+//! | TIM3  | channel 3, req 5  |                   |
+//! | TIM4  | channel 7, req 6  |                   |
+//! | TIM5  |                   | channel 2, req 5  |
+//! | TIM6  | channel 3, req 6  | channel 4, req 3  |
+//! | TIM7  | channel 4, req 5  | channel 5, req 3  |
+//! | TIM8  |                   | channel 1, req 7  |
+//! | TIM15 | channel 5, req 7  |                   |
+//! | TIM16 | channel 3, req 4  |                   |
+//! | TIM17 | channel 1, req 5  |                   |
+use void::Void;
+use cortex_m::peripheral::syst::SystClkSource;
+use cortex_m::peripheral::SYST;
+use embedded_hal::blocking::delay::{DelayMs, DelayUs};
+use embedded_hal::timer::{CountDown, Periodic};
+use nb;
+
+use crate::config::SYST_MAX_RVR;
+use crate::rcc::{APB1, APB2, Clocks};
+use crate::time::Hertz;
+
+use cast::{u16, u32};
+
+use core::cmp;
+
+use crate::gpio::{
+    AF1, //TIM1, TIM2
+    AF2, //TIM3, TIM4, TIM5
+    AF3, //TIM8
+    AF14, //TIM15, TIM16, TIM17
+    PA0, PA1, PA2, PA3, PA5, PA6, PA7, PA8, PA9, PA10, PA11, PA12, PA15,
+    PB0, PB1, PB3, PB4, PB5, PB6, PB7, PB8, PB9, PB10, PB11, PB12, PB13, PB14, PB15,
+    PC6, PC7, PC8, PC9,
+};
+#[cfg(feature = "GPIO_DE")]
+use crate::gpio::{PD2, PE0};
+
+use stm32l4x6::{
+    // advanced timers
+    TIM1,
+    TIM15,
+    TIM16,
+    TIM17, // stm32l4x6::rcc::apb2enr | apb2rstr
+    // General purpose
+    TIM2,
+    TIM3,
+    TIM4,
+    TIM5, // stm32l4x6::rcc::apb1enr1 | apb1rstr1
+    // Basic timers
+    TIM6,
+    TIM7, // stm32l4x6::rcc::apb1enr1 | apb1rstr1
+    // low-power timer LPTIM1 is implemented in lptim module
+    TIM8, // stm32l4x6::rcc::apb2enr | apb2rstr:
+};
+
+pub mod pwm;
+pub mod capture;
+pub mod compare;
+pub mod encoder;
+pub mod one_pulse;
+pub mod lptim;
+pub mod external;
+pub mod advanced;
+pub mod monotonic;
+
+/// Capture/Compare channel of timer
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum Channel {
+    /// Channel 1
+    CH1 = 1,
+    /// Channel 2
+    CH2 = 2,
+    /// Channel 3
+    CH3 = 3,
+    /// Channel 4
+    CH4 = 4,
+}
+
+impl Channel {
+    #[inline]
+    /// Offset of channel's bits within CCER.
+    pub(crate) fn ccer_offset(self) -> u8 {
+        4 * (self as u8 - 1)
+    }
+
+    #[inline]
+    /// Offset of channel's bits within its CCMRx register.
+    pub(crate) fn ccmr_offset(self) -> u8 {
+        match self {
+            Channel::CH1 | Channel::CH3 => 0,
+            Channel::CH2 | Channel::CH4 => 8,
+        }
+    }
+}
+
+///Describes pin of timer's channel
+pub trait Pin {
+    ///Timer index
+    const TIM_IDX: u8;
+    ///Timer's channel
+    const CHANNEL: Channel;
+}
+
+///Describes complementary output pin of advanced timer's channel
+pub trait PinN {
+    ///Timer index
+    const TIM_IDX: u8;
+    ///Timer's channel
+    const CHANNEL: Channel;
+}
+
+macro_rules! impl_pins_trait {
+    ($IDX:expr => {
+        CHANNEL: $CH:ident,
+        AF: $AFx:ident,
+        PINS: [$($PIN:ident,)+]
+    }) => {
+        impl_pins_trait!($IDX => {
+            TRAIT: Pin,
+            CHANNEL: $CH,
+            AF: $AFx,
+            PINS: [$($PIN,)+]
+        });
+    };
+    ($IDX:expr => {
+        TRAIT: $TRAIT:ident,
+        CHANNEL: $CH:ident,
+        AF: $AFx:ident,
+        PINS: [$($PIN:ident,)+]
+    }) => {
+        $(
+            impl $TRAIT for $PIN<$AFx> {
+                const TIM_IDX: u8 = $IDX;
+                const CHANNEL: Channel = Channel::$CH;
+            }
+        )+
+    }
+}
+
+impl_pins_trait!(1 => {
+    CHANNEL: CH1,
+    AF: AF1,
+    PINS: [PA8,]
+});
+impl_pins_trait!(1 => {
+    CHANNEL: CH2,
+    AF: AF1,
+    PINS: [PA9,]
+});
+impl_pins_trait!(1 => {
+    CHANNEL: CH3,
+    AF: AF1,
+    PINS: [PA10,]
+});
+impl_pins_trait!(1 => {
+    CHANNEL: CH4,
+    AF: AF1,
+    PINS: [PA11,]
+});
+impl_pins_trait!(1 => {
+    TRAIT: PinN,
+    CHANNEL: CH1,
+    AF: AF1,
+    PINS: [PA7, PB13,]
+});
+impl_pins_trait!(1 => {
+    TRAIT: PinN,
+    CHANNEL: CH2,
+    AF: AF1,
+    PINS: [PB0, PB14,]
+});
+impl_pins_trait!(1 => {
+    TRAIT: PinN,
+    CHANNEL: CH3,
+    AF: AF1,
+    PINS: [PB1, PB15,]
+});
+
+impl_pins_trait!(8 => {
+    CHANNEL: CH1,
+    AF: AF3,
+    PINS: [PC6,]
+});
+impl_pins_trait!(8 => {
+    CHANNEL: CH2,
+    AF: AF3,
+    PINS: [PC7,]
+});
+impl_pins_trait!(8 => {
+    CHANNEL: CH3,
+    AF: AF3,
+    PINS: [PC8,]
+});
+impl_pins_trait!(8 => {
+    CHANNEL: CH4,
+    AF: AF3,
+    PINS: [PC9,]
+});
+impl_pins_trait!(8 => {
+    TRAIT: PinN,
+    CHANNEL: CH1,
+    AF: AF3,
+    PINS: [PA5, PA7,]
+});
+impl_pins_trait!(8 => {
+    TRAIT: PinN,
+    CHANNEL: CH2,
+    AF: AF3,
+    PINS: [PB0, PB14,]
+});
+impl_pins_trait!(8 => {
+    TRAIT: PinN,
+    CHANNEL: CH3,
+    AF: AF3,
+    PINS: [PB1, PB15,]
+});
+
+impl_pins_trait!(2 => {
+    CHANNEL: CH1,
+    AF: AF1,
+    PINS: [PA0, PA5, PA15,]
+});
+impl_pins_trait!(2 => {
+    CHANNEL: CH2,
+    AF: AF1,
+    PINS: [PA1, PB3,]
+});
+impl_pins_trait!(2 => {
+    CHANNEL: CH3,
+    AF: AF1,
+    PINS: [PA2, PB10,]
+});
+impl_pins_trait!(2 => {
+    CHANNEL: CH4,
+    AF: AF1,
+    PINS: [PA3, PB11,]
+});
+
+impl_pins_trait!(3 => {
+    CHANNEL: CH1,
+    AF: AF2,
+    PINS: [PA6, PB4, PC6,]
+});
+impl_pins_trait!(3 => {
+    CHANNEL: CH2,
+    AF: AF2,
+    PINS: [PA7, PB5, PC7,]
+});
+impl_pins_trait!(3 => {
+    CHANNEL: CH3,
+    AF: AF2,
+    PINS: [PB0, PC8,]
+});
+impl_pins_trait!(3 => {
+    CHANNEL: CH4,
+    AF: AF2,
+    PINS: [PB1, PC9,]
+});
+
+impl_pins_trait!(4 => {
+    CHANNEL: CH1,
+    AF: AF2,
+    PINS: [PB6,]
+});
+impl_pins_trait!(4 => {
+    CHANNEL: CH2,
+    AF: AF2,
+    PINS: [PB7,]
+});
+impl_pins_trait!(4 => {
+    CHANNEL: CH3,
+    AF: AF2,
+    PINS: [PB8,]
+});
+impl_pins_trait!(4 => {
+    CHANNEL: CH4,
+    AF: AF2,
+    PINS: [PB9,]
+});
+
+impl_pins_trait!(5 => {
+    CHANNEL: CH1,
+    AF: AF2,
+    PINS: [PA0,]
+});
+impl_pins_trait!(5 => {
+    CHANNEL: CH2,
+    AF: AF2,
+    PINS: [PA1,]
+});
+impl_pins_trait!(5 => {
+    CHANNEL: CH3,
+    AF: AF2,
+    PINS: [PA2,]
+});
+impl_pins_trait!(5 => {
+    CHANNEL: CH4,
+    AF: AF2,
+    PINS: [PA3,]
+});
+
+//TIM15_CH2 (PA3, PB15) is not available as device crate lacks CCR2 of TIM15
+impl_pins_trait!(15 => {
+    CHANNEL: CH1,
+    AF: AF14,
+    PINS: [PA2, PB14,]
+});
+
+impl_pins_trait!(16 => {
+    CHANNEL: CH1,
+    AF: AF14,
+    PINS: [PA6, PB8,]
+});
+
+impl_pins_trait!(17 => {
+    CHANNEL: CH1,
+    AF: AF14,
+    PINS: [PA7, PB9,]
+});
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+/// Direction of counting
+pub enum Direction {
+    /// Counter is incremented
+    Up,
+    /// Counter is decremented
+    Down,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u8)]
+/// Center-aligned counting mode, in which counter counts up to ARR and then down to 0
+///
+/// Modes differ in direction of counting at which compare interrupt flags of output channels are set.
+pub enum CenterAligned {
+    /// Flags are set when counting down
+    Mode1 = 0b01,
+    /// Flags are set when counting up
+    Mode2 = 0b10,
+    /// Flags are set when counting both up and down
+    Mode3 = 0b11,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u8)]
+/// Source of trigger output (TRGO), used to synchronize other timers or peripherals
+///
+/// Reference Ch. 31.4.2 TIMx control register 2
+pub enum MasterMode {
+    /// UG bit of EGR
+    Reset = 0b000,
+    /// Counter enable
+    Enable = 0b001,
+    /// Update event
+    Update = 0b010,
+    /// Pulse on capture or compare match of channel 1
+    ComparePulse = 0b011,
+    /// OC1REF signal
+    Compare1 = 0b100,
+    /// OC2REF signal
+    Compare2 = 0b101,
+    /// OC3REF signal
+    Compare3 = 0b110,
+    /// OC4REF signal
+    Compare4 = 0b111,
+}
+
+///Describes external trigger (ETR) pin of timer
+pub trait ETR {
+    ///Timer index
+    const TIM_IDX: u8;
+}
+
+///Describes break input (BKIN) pin of advanced timer
+pub trait BKIN {
+    ///Timer index
+    const TIM_IDX: u8;
+}
+
+macro_rules! impl_tim_pins_trait {
+    ($IDX:expr => {
+        TRAIT: $TRAIT:ident,
+        AF: $AFx:ident,
+        PINS: [$($PIN:ident,)+]
+    }) => {
+        $(
+            impl $TRAIT for $PIN<$AFx> {
+                const TIM_IDX: u8 = $IDX;
+            }
+        )+
+    }
+}
+
+impl_tim_pins_trait!(1 => {
+    TRAIT: ETR,
+    AF: AF1,
+    PINS: [PA12,]
+});
+impl_tim_pins_trait!(2 => {
+    TRAIT: ETR,
+    AF: AF2,
+    PINS: [PA5, PA15,]
+});
+impl_tim_pins_trait!(2 => {
+    TRAIT: ETR,
+    AF: AF14,
+    PINS: [PA0,]
+});
+#[cfg(feature = "GPIO_DE")]
+impl_tim_pins_trait!(3 => {
+    TRAIT: ETR,
+    AF: AF2,
+    PINS: [PD2,]
+});
+#[cfg(feature = "GPIO_DE")]
+impl_tim_pins_trait!(4 => {
+    TRAIT: ETR,
+    AF: AF2,
+    PINS: [PE0,]
+});
+impl_tim_pins_trait!(8 => {
+    TRAIT: ETR,
+    AF: AF3,
+    PINS: [PA0,]
+});
+
+impl_tim_pins_trait!(1 => {
+    TRAIT: BKIN,
+    AF: AF1,
+    PINS: [PA6, PB12,]
+});
+impl_tim_pins_trait!(8 => {
+    TRAIT: BKIN,
+    AF: AF3,
+    PINS: [PA6, PB7,]
+});
+
+//UIF flag of SR
+const UIF: u32 = 1;
+
+/// Possible timer events
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Event {
+    /// Interrupt on timeout.
+    Timeout,
+    /// Interrupt on capture of channel's input.
+    ///
+    /// Shares CCxIE flag with `Compare`.
+    Capture(Channel),
+    /// Interrupt on compare match of channel's output.
+    ///
+    /// Shares CCxIE flag with `Capture`.
+    Compare(Channel),
+}
+
+/// HW Timer
+pub struct Timer<TIM> {
+    clocks: Clocks,
+    tim: TIM,
+}
+
+impl Timer<SYST> {
+    pub fn syst<T: Into<Hertz>>(mut syst: SYST, timeout: T, clocks: Clocks) -> Self {
+        syst.set_clock_source(SystClkSource::Core);
+        let mut timer = Timer { tim: syst, clocks };
+        timer.start(timeout);
+        timer
+    }
+
+    /// Starts listening for an `event`
+    ///
+    /// # Panics
+    ///
+    /// On capture/compare events, as SysTick has no channels.
+    pub fn subscribe(&mut self, event: Event) {
+        match event {
+            Event::Timeout => self.tim.enable_interrupt(),
+            Event::Capture(_) | Event::Compare(_) => panic!("SysTick has no channels"),
+        }
+    }
+
+    /// Stops listening for an `event`
+    ///
+    /// # Panics
+    ///
+    /// On capture/compare events, as SysTick has no channels.
+    pub fn unsubscribe(&mut self, event: Event) {
+        match event {
+            Event::Timeout => self.tim.disable_interrupt(),
+            Event::Capture(_) | Event::Compare(_) => panic!("SysTick has no channels"),
+        }
+    }
+}
+
+impl CountDown for Timer<SYST> {
+    type Time = Hertz;
+
+    fn start<T: Into<Hertz>>(&mut self, timeout: T) {
+        let rvr = self.clocks.sysclk.0 / timeout.into().0 - 1;
+
+        assert!(rvr < SYST_MAX_RVR);
+
+        self.tim.set_reload(rvr);
+        self.tim.clear_current();
+        self.tim.enable_counter();
+    }
+
+    fn wait(&mut self) -> nb::Result<(), Void> {
+        match self.tim.has_wrapped() {
+            true => Ok(()),
+            false => Err(nb::Error::WouldBlock),
+        }
+    }
+}
+
+macro_rules! impl_master_mode {
+    ($($TIMx:ident: [max: $max:path])+) => {
+        $(
+            impl Timer<$TIMx> {
+                /// Selects source of trigger output.
+                ///
+                /// # Panics
+                ///
+                /// When timer doesn't support mode. Basic timers TIM6 and TIM7 support
+                /// only `Reset`, `Enable` and `Update`.
+                pub fn set_master_mode(&mut self, mode: MasterMode) {
+                    assert!(mode as u8 <= $max as u8);
+                    self.tim.cr2.modify(|_, w| unsafe { w.mms().bits(mode as u8) });
+                }
+            }
+        )+
+    }
+}
+
+impl_master_mode!(
+    TIM1: [max: MasterMode::Compare4]
+    TIM8: [max: MasterMode::Compare4]
+    TIM2: [max: MasterMode::Compare4]
+    TIM3: [max: MasterMode::Compare4]
+    TIM4: [max: MasterMode::Compare4]
+    TIM5: [max: MasterMode::Compare4]
+    TIM6: [max: MasterMode::Update]
+    TIM7: [max: MasterMode::Update]
+);
+
+macro_rules! impl_count_mode {
+    ($($TIMx:ident)+) => {
+        $(
+            impl Timer<$TIMx> {
+                /// Sets direction of counting in edge-aligned mode.
+                ///
+                /// Switches timer out of center-aligned mode if it has been enabled.
+                pub fn set_direction(&mut self, dir: Direction) {
+                    let is_enabled = self.tim.cr1.read().cen().bit_is_set();
+
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    self.tim.cr1.modify(|_, w| unsafe { w.cms().bits(0).dir().bit(dir == Direction::Down) });
+                    self.tim.cr1.modify(|_, w| w.cen().bit(is_enabled));
+                }
+
+                /// Switches timer into center-aligned mode.
+                ///
+                /// Update event is generated both at overflow and at underflow, therefore
+                /// `CountDown` timeout remains the same, while period of output channels is doubled.
+                pub fn set_center_aligned(&mut self, mode: CenterAligned) {
+                    let is_enabled = self.tim.cr1.read().cen().bit_is_set();
+
+                    // Mode cannot be switched while counter is enabled
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    self.tim.cr1.modify(|_, w| unsafe { w.cms().bits(mode as u8) });
+                    self.tim.cr1.modify(|_, w| w.cen().bit(is_enabled));
+                }
+            }
+        )+
+    }
+}
+
+macro_rules! impl_basic_trgo {
+    ($($TIMx:ident)+) => {
+        $(
+            impl Timer<$TIMx> {
+                /// Sets update event as trigger output, so that DAC or ADC conversion
+                /// can be triggered at the timer's rate.
+                pub fn set_trgo_update(&mut self) {
+                    self.set_master_mode(MasterMode::Update);
+                }
+            }
+        )+
+    }
+}
+
+impl_basic_trgo!(TIM6 TIM7);
+
+// Basic timers and TIM15-17 can count only up
+impl_count_mode!(TIM1 TIM8 TIM2 TIM3 TIM4 TIM5);
+
+/// Calculates PSC and ARR values for timer with `clock` to overflow at `frequency`.
+///
+/// `max_arr` is the maximum value of ARR, which depends on width of timer's counter.
+/// Counter overflows every (PSC + 1) * (ARR + 1) ticks of `clock`.
+fn calc_psc_arr(clock: u32, frequency: u32, max_arr: u32) -> (u16, u32) {
+    let ticks = clock / frequency;
+    assert!(ticks > 0, "Frequency is higher than timer clock");
+
+    let psc = u16(u64::from(ticks - 1) / (u64::from(max_arr) + 1)).unwrap();
+    let arr = ticks / (u32(psc) + 1) - 1;
+    assert!(arr <= max_arr);
+
+    (psc, arr)
+}
+
+/// Type alias for timer based on system clock.
+pub type Sys = Timer<SYST>;
+
+macro_rules! impl_timer {
+    ($($TIMx:ident: [alias: $Alias:ident; constructor: $timx:ident; counter: $Cnt:ty; channels: $channels:expr; $APB:ident: {apb: $apb:ident; $enr:ident: $enr_bit:ident; $rstr:ident: $rstr_bit:ident; ppre: $ppre:ident}])+) => {
+        $(
+            ///Type alias for TIM timer.
+            pub type $Alias = Timer<$TIMx>;
+
+            impl Timer<$TIMx> {
+                ///Creates new instance of timer.
+                pub fn $timx<T: Into<Hertz>>(tim: $TIMx, timeout: T, clocks: Clocks, apb: &mut $APB) -> Timer<$TIMx> {
+                    // enable and reset peripheral to a clean slate state
+                    apb.$enr().modify(|_, w| w.$enr_bit().set_bit());
+                    apb.$rstr().modify(|_, w| w.$rstr_bit().set_bit());
+                    apb.$rstr().modify(|_, w| w.$rstr_bit().clear_bit());
+
+                    let mut timer = Timer {
+                        clocks,
+                        tim,
+                    };
+                    timer.start(timeout);
+
+                    timer
+                }
+
+                /// Starts listening for an `event`
+                ///
+                /// # Panics
+                ///
+                /// When timer doesn't have channel of capture/compare event.
+                pub fn subscribe(&mut self, event: Event) {
+                    match event {
+                        Event::Timeout => self.tim.dier.modify(|_, w| w.uie().set_bit()),
+                        Event::Capture(channel) | Event::Compare(channel) => {
+                            let flag = Self::channel_flag(channel);
+                            self.tim.dier.modify(|r, w| unsafe { w.bits(r.bits() | flag) });
+                        }
+                    }
+                }
+
+                /// Stops listening for an `event`
+                ///
+                /// # Panics
+                ///
+                /// When timer doesn't have channel of capture/compare event.
+                pub fn unsubscribe(&mut self, event: Event) {
+                    match event {
+                        Event::Timeout => self.tim.dier.modify(|_, w| w.uie().clear_bit()),
+                        Event::Capture(channel) | Event::Compare(channel) => {
+                            let flag = Self::channel_flag(channel);
+                            self.tim.dier.modify(|r, w| unsafe { w.bits(r.bits() & !flag) });
+                        }
+                    }
+                }
+
+                /// Returns whether `event` has occurred and its flag has not been cleared yet.
+                ///
+                /// # Panics
+                ///
+                /// When timer doesn't have channel of capture/compare event.
+                pub fn is_pending(&self, event: Event) -> bool {
+                    match event {
+                        Event::Timeout => self.tim.sr.read().uif().bit_is_set(),
+                        Event::Capture(channel) | Event::Compare(channel) => self.tim.sr.read().bits() & Self::channel_flag(channel) != 0,
+                    }
+                }
+
+                /// Clears flag of `event`.
+                ///
+                /// It must be done in interrupt handler, otherwise handler is going to be called again.
+                ///
+                /// # Panics
+                ///
+                /// When timer doesn't have channel of capture/compare event.
+                pub fn clear_interrupt(&mut self, event: Event) {
+                    match event {
+                        Event::Timeout => self.reset_overflow(),
+                        // NOTE(unsafe) SR flags are cleared by writing 0, writing 1 has no effect
+                        Event::Capture(channel) | Event::Compare(channel) => {
+                            let flag = Self::channel_flag(channel);
+                            self.tim.sr.write(|w| unsafe { w.bits(!flag) });
+                        }
+                    }
+                }
+
+                #[inline]
+                /// Retrieves CCxIE flag of DIER, which is at the same position as CCxIF of SR.
+                fn channel_flag(channel: Channel) -> u32 {
+                    assert!(channel as u8 <= $channels, "Timer has no such channel");
+                    1 << (channel as u8)
+                }
+
+                #[inline(always)]
+                /// Resets SR's UIF register to clear status of overflow.
+                ///
+                /// Unless reset is done, Interrupt handler is going to be continiously called.
+                pub fn reset_overflow(&mut self) {
+                    // NOTE(unsafe) SR flags are cleared by writing 0, writing 1 has no effect,
+                    // unlike modify, which could clear flags of channels set meanwhile
+                    self.tim.sr.write(|w| unsafe { w.bits(!UIF) });
+                }
+
+                /// Enables DMA request on update event.
+                pub fn enable_update_dma(&mut self) {
+                    self.tim.dier.modify(|_, w| w.ude().set_bit());
+                }
+
+                /// Disables DMA request on update event.
+                pub fn disable_update_dma(&mut self) {
+                    self.tim.dier.modify(|_, w| w.ude().clear_bit());
+                }
+
+                /// Retrieves address of ARR, to be used as peripheral address of DMA transfer.
+                pub fn arr_address(&self) -> u32 {
+                    &self.tim.arr as *const _ as u32
+                }
+
+                /// Changes frequency of timer without stopping it.
+                ///
+                /// Configuration of channels is preserved, but their compare values are not rescaled.
+                /// Counter is reset, while no update interrupt is raised.
+                pub fn set_frequency<T: Into<Hertz>>(&mut self, freq: T) {
+                    let (psc, arr) = calc_psc_arr(self.clock(), freq.into().0, <$Cnt>::MAX as u32);
+                    self.tim.psc.write(|w| unsafe { w.psc().bits(psc) });
+                    self.tim.arr.write(|w| unsafe { w.bits(arr) });
+
+                    // Load new values at once, with URS preventing UG from setting UIF
+                    let urs = self.tim.cr1.read().urs().bit();
+                    self.tim.cr1.modify(|_, w| w.urs().set_bit());
+                    self.tim.egr.write(|w| w.ug().set_bit());
+                    self.tim.cr1.modify(|_, w| w.urs().bit(urs));
+                }
+
+                /// Pauses timer, keeping its configuration.
+                pub fn stop(&mut self) {
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                }
+
+                /// Resumes timer paused by [stop](#method.stop), without resetting its counter.
+                pub fn resume(&mut self) {
+                    self.tim.cr1.modify(|_, w| w.cen().set_bit());
+                }
+
+                /// Retrieves current value of counter.
+                ///
+                /// TIM2 and TIM5 have 32-bit counter, while other timers are 16-bit.
+                pub fn counter(&self) -> u32 {
+                    self.tim.cnt.read().bits() as $Cnt as u32
+                }
+
+                /// Retrieves time left until next update event, in microseconds.
+                ///
+                /// It is time until counter reaches auto-reload value when counting up, or 0 when counting down.
+                /// In center-aligned mode it is the same for current direction, i.e. half of period at most.
+                pub fn remaining(&self) -> u32 {
+                    //DIR of CR1, reserved and read as 0 by timers, that count only up.
+                    //In center-aligned mode it reflects current direction.
+                    const DIR: u32 = 1 << 4;
+
+                    let arr = self.tim.arr.read().bits() as $Cnt as u64;
+                    let cnt = self.counter() as u64;
+                    let psc = self.tim.psc.read().bits() as u64;
+                    let ticks = match self.tim.cr1.read().bits() & DIR {
+                        0 => arr.saturating_sub(cnt),
+                        _ => cnt,
+                    };
+
+                    (ticks * (psc + 1) * 1_000_000 / self.clock() as u64) as u32
+                }
+
+                #[inline]
+                /// Retrieves frequency of timer's input clock.
+                ///
+                /// When APB prescaler is not 1, timer's clock is twice the frequency of APB.
+                fn clock(&self) -> u32 {
+                    let ppre = match self.clocks.$ppre {
+                        1 => 1,
+                        _ => 2
+                    };
+                    self.clocks.$apb.0 * ppre
+                }
+
+                /// Paused timer and releases the TIM peripheral
+                pub fn free(self) -> $TIMx {
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    self.tim
+                }
+
+            }
+
+            impl DelayMs<u32> for Timer<$TIMx> {
+                fn delay_ms(&mut self, ms: u32) {
+                    // Microseconds of longer delays do not fit into u32
+                    const MAX_MS: u32 = u32::MAX / 1_000;
+
+                    let mut ms = ms;
+                    while ms > MAX_MS {
+                        self.delay_us(MAX_MS * 1_000);
+                        ms -= MAX_MS;
+                    }
+                    self.delay_us(ms * 1_000);
+                }
+            }
+
+            impl DelayMs<u16> for Timer<$TIMx> {
+                fn delay_ms(&mut self, ms: u16) {
+                    self.delay_ms(u32(ms));
+                }
+            }
+
+            impl DelayMs<u8> for Timer<$TIMx> {
+                fn delay_ms(&mut self, ms: u8) {
+                    self.delay_ms(u32(ms));
+                }
+            }
+
+            impl DelayUs<u32> for Timer<$TIMx> {
+                /// Blocks for `us` microseconds.
+                ///
+                /// Counter is set to run at 1 MHz, so timer's clock must be at least 1 MHz.
+                /// Delays longer than single period of the counter are split into several.
+                ///
+                /// Note that it overrides timeout, therefore `start` must be called
+                /// before timer can be used as `CountDown` again.
+                fn delay_us(&mut self, us: u32) {
+                    let clock = self.clock();
+                    assert!(clock >= 1_000_000);
+
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    let psc = u16(clock / 1_000_000 - 1).unwrap();
+                    self.tim.psc.write(|w| unsafe { w.psc().bits(psc) });
+
+                    let mut total = us;
+                    while total != 0 {
+                        let current = cmp::min(total, <$Cnt>::MAX as u32);
+
+                        self.tim.arr.write(|w| unsafe { w.bits(current) });
+                        self.tim.cnt.reset();
+                        // Load prescaler and ARR
+                        self.tim.egr.write(|w| w.ug().set_bit());
+                        self.reset_overflow();
+                        self.tim.cr1.modify(|_, w| w.cen().set_bit());
+
+                        total -= current;
+
+                        while self.tim.sr.read().uif().bit_is_clear() {}
+
+                        self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    }
+                    self.reset_overflow();
+                }
+            }
+
+            impl DelayUs<u16> for Timer<$TIMx> {
+                fn delay_us(&mut self, us: u16) {
+                    self.delay_us(u32(us))
+                }
+            }
+
+            impl DelayUs<u8> for Timer<$TIMx> {
+                fn delay_us(&mut self, us: u8) {
+                    self.delay_us(u32(us))
+                }
+            }
+
+            impl Periodic for Timer<$TIMx> {}
+            impl CountDown for Timer<$TIMx> {
+                type Time = Hertz;
+
+                fn start<T: Into<Self::Time>>(&mut self, timeout: T) {
+                    //pause
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    //reset counter's value
+                    self.tim.cnt.reset();
+
+                    let frequency = timeout.into().0;
+
+                    let (psc, arr) = calc_psc_arr(self.clock(), frequency, <$Cnt>::MAX as u32);
+                    self.tim.psc.write(|w| unsafe { w.psc().bits(psc) });
+                    self.tim.arr.write(|w| unsafe { w.bits(arr) });
+
+                    // Trigger an update event to load the prescaler value to the clock
+                    self.tim.egr.write(|w| w.ug().set_bit());
+                    // The above line raises an update event which will indicate
+                    // that the timer is already finnished. Since this is not the case,
+                    // it should be cleared
+                    self.reset_overflow();
+
+                    // start counter
+                    self.tim.cr1.modify(|_, w| w.cen().set_bit());
+                }
+
+                 fn wait(&mut self) -> nb::Result<(), Void> {
+                     match self.tim.sr.read().uif().bit_is_clear() {
+                         true => Err(nb::Error::WouldBlock),
+                         false => {
+                             self.reset_overflow();
+                             Ok(())
+                         }
+                     }
+                 }
+            }
+
+        )+
+    }
+}
+
+impl_timer!(
+    TIM1: [
+        alias: Tim1;
+        constructor: tim1;
+        counter: u16;
+        channels: 4;
+        APB2: {
+            apb: pclk2;
+            enr: tim1en;
+            rstr: tim1rst;
+            ppre: ppre2
+        }
+    ]
+    TIM8: [
+        alias: Tim8;
+        constructor: tim8;
+        counter: u16;
+        channels: 4;
+        APB2: {
+            apb: pclk2;
+            enr: tim8en;
+            rstr: tim8rst;
+            ppre: ppre2
+        }
+    ]
+    TIM2: [
+        alias: Tim2;
+        constructor: tim2;
+        counter: u32;
+        channels: 4;
+        APB1: {
+            apb: pclk1;
+            enr1: tim2en;
+            rstr1: tim2rst;
+            ppre: ppre1
+        }
+    ]
+    TIM3: [
+        alias: Tim3;
+        constructor: tim3;
+        counter: u16;
+        channels: 4;
+        APB1: {
+            apb: pclk1;
+            enr1: tim3en;
+            rstr1: tim3rst;
+            ppre: ppre1
+        }
+    ]
+    TIM4: [
+        alias: Tim4;
+        constructor: tim4;
+        counter: u16;
+        channels: 4;
+        APB1: {
+            apb: pclk1;
+            enr1: tim4en;
+            rstr1: tim4rst;
+            ppre: ppre1
+        }
+    ]
+    TIM5: [
+        alias: Tim5;
+        constructor: tim5;
+        counter: u32;
+        channels: 4;
+        APB1: {
+            apb: pclk1;
+            enr1: tim5en;
+            rstr1: tim5rst;
+            ppre: ppre1
+        }
+    ]
+    TIM15: [
+        alias: Tim15;
+        constructor: tim15;
+        counter: u16;
+        channels: 2;
+        APB2: {
+            apb: pclk2;
+            enr: tim15en;
+            rstr: tim15rst;
+            ppre: ppre2
+        }
+    ]
+    TIM16: [
+        alias: Tim16;
+        constructor: tim16;
+        counter: u16;
+        channels: 1;
+        APB2: {
+            apb: pclk2;
+            enr: tim16en;
+            rstr: tim16rst;
+            ppre: ppre2
+        }
+    ]
+    TIM17: [
+        alias: Tim17;
+        constructor: tim17;
+        counter: u16;
+        channels: 1;
+        APB2: {
+            apb: pclk2;
+            enr: tim17en;
+            rstr: tim17rst;
+            ppre: ppre2
+        }
+    ]
+    TIM6: [
+        alias: Tim6;
+        constructor: tim6;
+        counter: u16;
+        channels: 0;
+        APB1: {
+            apb: pclk1;
+            enr1: tim6en;
+            rstr1: tim6rst;
+            ppre: ppre1
+        }
+    ]
+    TIM7: [
+        alias: Tim7;
+        constructor: tim7;
+        counter: u16;
+        channels: 0;
+        APB1: {
+            apb: pclk1;
+            enr1: tim7en;
+            rstr1: tim7rst;
+            ppre: ppre1
+        }
+    ]
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn calculate_psc_arr() {
+        let clock = 80_000_000;
+
+        // 16-bit counter requires prescaler for long timeout
+        let (psc, arr) = calc_psc_arr(clock, 1, 0xFFFF);
+        assert_eq!(psc, 1220);
+        assert_eq!(arr, 65519);
+
+        // 32-bit counter of TIM2/TIM5 fits it into ARR
+        let (psc, arr) = calc_psc_arr(clock, 1, 0xFFFF_FFFF);
+        assert_eq!(psc, 0);
+        assert_eq!(arr, 79_999_999);
+
+        let (psc, arr) = calc_psc_arr(clock, 1_000, 0xFFFF);
+        assert_eq!(psc, 1);
+        assert_eq!(arr, 39_999);
+    }
+}
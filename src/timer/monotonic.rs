@@ -0,0 +1,130 @@
+//! Monotonic clock
+//!
+//! Counter of timer is extended with number of its overflows into 64-bit value, which doesn't wrap
+//! within lifetime of application.
+//!
+//! Overflows are counted in update interrupt, therefore its handler must call
+//! [on_overflow](struct.MonoTimer.html#method.on_overflow):
+//!
+//! ```rust, ignore
+//! static MONO: Mutex<RefCell<Option<MonoTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+//!
+//! #[interrupt]
+//! fn TIM2() {
+//!     cortex_m::interrupt::free(|cs| {
+//!         if let Some(mono) = MONO.borrow(cs).borrow().as_ref() {
+//!             mono.on_overflow();
+//!         }
+//!     });
+//! }
+//! ```
+//!
+//! Besides that interrupt of the timer must be unmasked in NVIC.
+use core::cell::Cell;
+
+use cast::{u16, u64};
+use cortex_m::interrupt::{self, Mutex};
+
+use stm32l4x6::{TIM1, TIM2, TIM3, TIM4, TIM5, TIM6, TIM7, TIM8, TIM15, TIM16, TIM17};
+
+use super::{Event, Timer};
+use crate::time::Hertz;
+
+//UIF of SR, other flags are not affected by writing 1
+const UIF: u32 = 1;
+
+/// Timer, that counts ticks monotonically.
+pub struct MonoTimer<TIM> {
+    timer: Timer<TIM>,
+    // Cortex-M4 lacks 64-bit atomics, hence critical section
+    overflows: Mutex<Cell<u64>>,
+}
+
+macro_rules! impl_monotonic {
+    ($($TIMx:ident: $Cnt:ty,)+) => {
+        $(
+            impl MonoTimer<$TIMx> {
+                /// Creates new monotonic clock, ticking with frequency `freq`.
+                ///
+                /// Subscribes to timeout of the timer, to count overflows.
+                ///
+                /// # Panics
+                ///
+                /// When `freq` cannot be derived from timer's clock.
+                pub fn new<T: Into<Hertz>>(mut timer: Timer<$TIMx>, freq: T) -> Self {
+                    let psc = u16(timer.clock() / freq.into().0 - 1).unwrap();
+
+                    timer.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    timer.tim.cnt.reset();
+                    timer.tim.psc.write(|w| unsafe { w.psc().bits(psc) });
+                    timer.tim.arr.write(|w| unsafe { w.bits(<$Cnt>::MAX as u32) });
+                    // Load the prescaler without raising update event
+                    timer.tim.cr1.modify(|_, w| w.urs().set_bit());
+                    timer.tim.egr.write(|w| w.ug().set_bit());
+                    timer.tim.cr1.modify(|_, w| w.urs().clear_bit());
+
+                    timer.reset_overflow();
+                    timer.subscribe(Event::Timeout);
+                    timer.tim.cr1.modify(|_, w| w.cen().set_bit());
+
+                    Self {
+                        timer,
+                        overflows: Mutex::new(Cell::new(0)),
+                    }
+                }
+
+                /// Handles overflow of the counter.
+                ///
+                /// Must be called from update interrupt of the timer.
+                pub fn on_overflow(&self) {
+                    if self.timer.tim.sr.read().uif().bit_is_set() {
+                        // Flags of channels may be set meanwhile, so SR is not modified
+                        self.timer.tim.sr.write(|w| unsafe { w.bits(!UIF) });
+                        interrupt::free(|cs| {
+                            let overflows = self.overflows.borrow(cs);
+                            overflows.set(overflows.get() + 1);
+                        });
+                    }
+                }
+
+                /// Retrieves number of ticks passed since creation.
+                pub fn now(&self) -> u64 {
+                    interrupt::free(|cs| {
+                        let overflows = self.overflows.borrow(cs).get();
+                        let cnt = self.timer.counter();
+                        // Overflow may happen before interrupt is handled
+                        let is_pending = self.timer.tim.sr.read().uif().bit_is_set();
+
+                        let overflows = match is_pending && cnt <= <$Cnt>::MAX as u32 / 2 {
+                            true => overflows + 1,
+                            false => overflows,
+                        };
+
+                        overflows * (u64(<$Cnt>::MAX) + 1) + u64(cnt)
+                    })
+                }
+
+                /// Stops counting and releases the timer.
+                pub fn free(mut self) -> Timer<$TIMx> {
+                    self.timer.unsubscribe(Event::Timeout);
+                    self.timer.stop();
+                    self.timer
+                }
+            }
+        )+
+    }
+}
+
+impl_monotonic!(
+    TIM1: u16,
+    TIM8: u16,
+    TIM2: u32,
+    TIM3: u16,
+    TIM4: u16,
+    TIM5: u32,
+    TIM15: u16,
+    TIM16: u16,
+    TIM17: u16,
+    TIM6: u16,
+    TIM7: u16,
+);
@@ -0,0 +1,186 @@
+//! One-pulse mode
+//!
+//! After trigger timer outputs single pulse on its channel and then stops.
+//! Pulse begins after `delay` and lasts for `width`, both expressed in ticks of counter
+//! whose rate is defined by prescaler, configured when timer has been started.
+//!
+//! Reference Ch. 31.3.16 One-pulse mode
+use stm32l4x6::{TIM2, TIM3, TIM4, TIM5};
+
+use super::{Channel, Pin, Timer};
+
+//OCxM of CCMRx occupies bits 4-6 and bit 16
+const OCM_MASK: u32 = (0b111 << 4) | (1 << 16);
+//PWM mode 2: channel is active as long as CNT >= CCRx
+const OCM_PWM2: u32 = 0b111 << 4;
+//Capture/Compare selection, 00 sets channel as output
+const CCS_MASK: u32 = 0b11;
+const CCE: u32 = 1;
+
+//SMCR's SMS value that starts counter on trigger
+const SMS_TRIGGER: u8 = 0b110;
+
+#[derive(Copy, Clone)]
+#[repr(u8)]
+/// Source that starts pulse
+pub enum Trigger {
+    /// Pulse is started by `fire`
+    Software = 0,
+    /// Filtered input of channel 1
+    ///
+    /// Channel 1 must be configured as input, e.g. by means of capture.
+    TI1 = 0b101,
+    /// Filtered input of channel 2
+    ///
+    /// Channel 2 must be configured as input, e.g. by means of capture.
+    TI2 = 0b110,
+}
+
+/// Timer in one-pulse mode.
+pub struct OnePulse<TIM, PIN> {
+    timer: Timer<TIM>,
+    pin: PIN,
+}
+
+macro_rules! impl_one_pulse {
+    ($($TIMx:ident: [idx: $idx:expr; $(max: $max:expr;)? channels: [$($CH:ident => {ccmr: $ccmr:ident; ccr: $ccr:ident},)+]])+) => {
+        $(
+            impl Timer<$TIMx> {
+                /// Switches timer into one-pulse mode, outputting pulse on channel of the `pin`.
+                ///
+                /// Counter is stopped until pulse is triggered.
+                ///
+                /// # Panics
+                ///
+                /// When pulse doesn't fit counter's range.
+                ///
+                /// In debug mode the function checks that `pin` belongs to the timer.
+                pub fn one_pulse<PIN: Pin>(self, pin: PIN, delay: u32, width: u32, trigger: Trigger) -> OnePulse<$TIMx, PIN> {
+                    debug_assert_eq!(PIN::TIM_IDX, $idx);
+
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit().opm().set_bit());
+                    self.tim.cnt.reset();
+
+                    let offset = PIN::CHANNEL.ccmr_offset();
+                    #[allow(unreachable_patterns)]
+                    match PIN::CHANNEL {
+                        $(
+                            Channel::$CH => self.tim.$ccmr.modify(|r, w| unsafe {
+                                w.bits((r.bits() & !((OCM_MASK | CCS_MASK) << offset)) | (OCM_PWM2 << offset))
+                            }),
+                        )+
+                        _ => unreachable!(),
+                    }
+
+                    match trigger {
+                        Trigger::Software => self.tim.smcr.modify(|_, w| unsafe { w.sms().bits(0) }),
+                        trigger => self.tim.smcr.modify(|_, w| unsafe { w.ts().bits(trigger as u8).sms().bits(SMS_TRIGGER) }),
+                    }
+
+                    let mut one_pulse = OnePulse {
+                        timer: self,
+                        pin
+                    };
+                    one_pulse.set_pulse(delay, width);
+
+                    let offset = PIN::CHANNEL.ccer_offset();
+                    one_pulse.timer.tim.ccer.modify(|r, w| unsafe { w.bits(r.bits() | (CCE << offset)) });
+
+                    one_pulse
+                }
+            }
+
+            impl<PIN: Pin> OnePulse<$TIMx, PIN> {
+                /// Sets delay and width of pulse.
+                ///
+                /// Takes effect on next pulse.
+                ///
+                /// # Panics
+                ///
+                /// When `width` is zero or pulse doesn't fit counter's range.
+                pub fn set_pulse(&mut self, delay: u32, width: u32) {
+                    assert!(width > 0);
+                    let arr = delay.checked_add(width - 1).expect("Pulse overflows counter");
+                    $(
+                        assert!(arr <= $max);
+                    )?
+
+                    #[allow(unreachable_patterns)]
+                    match PIN::CHANNEL {
+                        $(
+                            Channel::$CH => self.timer.tim.$ccr.write(|w| unsafe { w.bits(delay) }),
+                        )+
+                        _ => unreachable!(),
+                    }
+                    self.timer.tim.arr.write(|w| unsafe { w.bits(arr) });
+                }
+
+                /// Starts pulse.
+                ///
+                /// Has no effect while previous pulse is in progress.
+                pub fn fire(&mut self) {
+                    self.timer.tim.cr1.modify(|_, w| w.cen().set_bit());
+                }
+
+                /// Returns whether pulse is in progress.
+                pub fn is_running(&self) -> bool {
+                    self.timer.tim.cr1.read().cen().bit_is_set()
+                }
+
+                /// Disables output and switches timer back into periodic mode.
+                ///
+                /// Timer remains stopped until it is started again.
+                pub fn free(self) -> (Timer<$TIMx>, PIN) {
+                    let tim = &self.timer.tim;
+                    let offset = PIN::CHANNEL.ccer_offset();
+                    tim.ccer.modify(|r, w| unsafe { w.bits(r.bits() & !(CCE << offset)) });
+                    tim.smcr.modify(|_, w| unsafe { w.sms().bits(0) });
+                    tim.cr1.modify(|_, w| w.cen().clear_bit().opm().clear_bit());
+
+                    (self.timer, self.pin)
+                }
+            }
+        )+
+    }
+}
+
+impl_one_pulse!(
+    TIM2: [
+        idx: 2;
+        channels: [
+            CH1 => {ccmr: ccmr1_output; ccr: ccr1},
+            CH2 => {ccmr: ccmr1_output; ccr: ccr2},
+            CH3 => {ccmr: ccmr2_output; ccr: ccr3},
+            CH4 => {ccmr: ccmr2_output; ccr: ccr4},
+        ]
+    ]
+    TIM3: [
+        idx: 3;
+        max: 0xFFFF;
+        channels: [
+            CH1 => {ccmr: ccmr1_output; ccr: ccr1},
+            CH2 => {ccmr: ccmr1_output; ccr: ccr2},
+            CH3 => {ccmr: ccmr2_output; ccr: ccr3},
+            CH4 => {ccmr: ccmr2_output; ccr: ccr4},
+        ]
+    ]
+    TIM4: [
+        idx: 4;
+        max: 0xFFFF;
+        channels: [
+            CH1 => {ccmr: ccmr1_output; ccr: ccr1},
+            CH2 => {ccmr: ccmr1_output; ccr: ccr2},
+            CH3 => {ccmr: ccmr2_output; ccr: ccr3},
+            CH4 => {ccmr: ccmr2_output; ccr: ccr4},
+        ]
+    ]
+    TIM5: [
+        idx: 5;
+        channels: [
+            CH1 => {ccmr: ccmr1_output; ccr: ccr1},
+            CH2 => {ccmr: ccmr1_output; ccr: ccr2},
+            CH3 => {ccmr: ccmr2_output; ccr: ccr3},
+            CH4 => {ccmr: ccmr2_output; ccr: ccr4},
+        ]
+    ]
+);
@@ -0,0 +1,330 @@
+//! Pulse Width Modulation
+//!
+//! PWM signal is generated by timer's channel, while frequency of the signal
+//! is the frequency of the timer itself.
+//!
+//! Reference Ch. 31.3.10 PWM mode
+use core::marker::PhantomData;
+
+use embedded_hal::PwmPin;
+
+use stm32l4x6::{TIM1, TIM2, TIM3, TIM4, TIM8, TIM15, TIM16, TIM17};
+
+use super::{CenterAligned, Channel, Pin, PinN, Timer};
+
+//OCxM of CCMRx occupies bits 4-6 and bit 16
+const OCM_MASK: u32 = (0b111 << 4) | (1 << 16);
+//PWM mode 1: channel is active as long as CNT < CCRx
+const OCM_PWM1: u32 = 0b110 << 4;
+//Output compare preload enable
+const OCPE: u32 = 1 << 3;
+//Capture/Compare selection, 00 sets channel as output
+const CCS_MASK: u32 = 0b11;
+
+//CCxE, CCxNE and CCxNP of CCER
+const CCE: u32 = 1;
+const CCNE: u32 = 1 << 2;
+const CCNP: u32 = 1 << 3;
+
+/// PWM output on timer's channel.
+pub struct Pwm<TIM, PIN> {
+    pin: PIN,
+    _tim: PhantomData<TIM>,
+}
+
+/// PWM output on advanced timer's channel together with its complementary output.
+///
+/// Complementary output is active while main output is not, with dead-time inserted
+/// between transitions of both outputs.
+pub struct ComplementaryPwm<TIM, PIN, NPIN> {
+    pwm: Pwm<TIM, PIN>,
+    npin: NPIN,
+}
+
+macro_rules! impl_pwm {
+    ($($TIMx:ident: [idx: $idx:expr; duty: $Duty:ty; $(bdtr: $bdtr:ident;)? channels: [$($CH:ident => {ccmr: $ccmr:ident; ccr: $ccr:ident},)+]])+) => {
+        $(
+            impl Timer<$TIMx> {
+                /// Configures channel of the `pin` to output PWM.
+                ///
+                /// Channel is set into PWM mode 1 with zero duty, and its output is disabled until
+                /// `enable` is called.
+                ///
+                /// # Panics
+                ///
+                /// In debug mode the function checks that `pin` belongs to the timer.
+                pub fn pwm<PIN: Pin>(&mut self, pin: PIN) -> Pwm<$TIMx, PIN> {
+                    debug_assert_eq!(PIN::TIM_IDX, $idx);
+
+                    let offset = PIN::CHANNEL.ccmr_offset();
+                    #[allow(unreachable_patterns)]
+                    match PIN::CHANNEL {
+                        $(
+                            Channel::$CH => {
+                                self.tim.$ccr.reset();
+                                self.tim.$ccmr.modify(|r, w| unsafe {
+                                    w.bits((r.bits() & !((OCM_MASK | CCS_MASK) << offset)) | ((OCM_PWM1 | OCPE) << offset))
+                                });
+                            },
+                        )+
+                        _ => unreachable!(),
+                    }
+
+                    $(
+                        //Outputs of timers with break feature are gated by MOE
+                        self.tim.$bdtr.modify(|_, w| w.moe().set_bit());
+                    )?
+
+                    Pwm {
+                        pin,
+                        _tim: PhantomData
+                    }
+                }
+            }
+
+            impl<PIN: Pin> Pwm<$TIMx, PIN> {
+                /// Retrieves address of channel's CCRx, to be used as peripheral address of DMA transfer.
+                ///
+                /// Combined with DMA request on update event, it allows to change duty on each period.
+                pub fn duty_address(&self) -> u32 {
+                    let tim = unsafe { &(*$TIMx::ptr()) };
+                    #[allow(unreachable_patterns)]
+                    match PIN::CHANNEL {
+                        $(
+                            Channel::$CH => &tim.$ccr as *const _ as u32,
+                        )+
+                        _ => unreachable!(),
+                    }
+                }
+
+                /// Disables channel's output and releases its pin.
+                pub fn release(mut self) -> PIN {
+                    self.disable();
+                    self.pin
+                }
+            }
+
+            impl<PIN: Pin> PwmPin for Pwm<$TIMx, PIN> {
+                type Duty = $Duty;
+
+                fn disable(&mut self) {
+                    let offset = PIN::CHANNEL.ccer_offset();
+                    // NOTE(unsafe) CCxE bit of channel is owned by this instance
+                    unsafe { (*$TIMx::ptr()).ccer.modify(|r, w| w.bits(r.bits() & !(CCE << offset))) };
+                }
+
+                fn enable(&mut self) {
+                    let offset = PIN::CHANNEL.ccer_offset();
+                    // NOTE(unsafe) CCxE bit of channel is owned by this instance
+                    unsafe { (*$TIMx::ptr()).ccer.modify(|r, w| w.bits(r.bits() | (CCE << offset))) };
+                }
+
+                fn get_duty(&self) -> Self::Duty {
+                    let tim = unsafe { &(*$TIMx::ptr()) };
+                    #[allow(unreachable_patterns)]
+                    match PIN::CHANNEL {
+                        $(
+                            Channel::$CH => tim.$ccr.read().bits() as $Duty,
+                        )+
+                        _ => unreachable!(),
+                    }
+                }
+
+                fn get_max_duty(&self) -> Self::Duty {
+                    unsafe { (*$TIMx::ptr()).arr.read().bits() as $Duty }
+                }
+
+                fn set_duty(&mut self, duty: Self::Duty) {
+                    // NOTE(unsafe) CCRx of channel is owned by this instance
+                    let tim = unsafe { &(*$TIMx::ptr()) };
+                    #[allow(unreachable_patterns)]
+                    match PIN::CHANNEL {
+                        $(
+                            Channel::$CH => tim.$ccr.write(|w| unsafe { w.bits(duty as u32) }),
+                        )+
+                        _ => unreachable!(),
+                    }
+                }
+            }
+        )+
+    }
+}
+
+macro_rules! impl_complementary_pwm {
+    ($($TIMx:ident)+) => {
+        $(
+            impl<PIN: Pin> Pwm<$TIMx, PIN> {
+                /// Adds complementary output on `npin` to the channel.
+                ///
+                /// # Panics
+                ///
+                /// In debug mode the function checks that `npin` belongs to the same channel.
+                pub fn complementary<NPIN: PinN>(mut self, npin: NPIN) -> ComplementaryPwm<$TIMx, PIN, NPIN> {
+                    debug_assert_eq!(PIN::TIM_IDX, NPIN::TIM_IDX);
+                    debug_assert_eq!(PIN::CHANNEL, NPIN::CHANNEL);
+
+                    self.disable();
+
+                    ComplementaryPwm {
+                        pwm: self,
+                        npin
+                    }
+                }
+            }
+
+            impl<PIN: Pin, NPIN: PinN> ComplementaryPwm<$TIMx, PIN, NPIN> {
+                /// Sets whether complementary output is active low.
+                pub fn set_complementary_inverted(&mut self, is_inverted: bool) {
+                    let flag = CCNP << PIN::CHANNEL.ccer_offset();
+                    unsafe { (*$TIMx::ptr()).ccer.modify(|r, w| w.bits(match is_inverted {
+                        true => r.bits() | flag,
+                        false => r.bits() & !flag,
+                    })) };
+                }
+
+                /// Disables both outputs and releases their pins.
+                pub fn release(mut self) -> (PIN, NPIN) {
+                    self.disable();
+                    (self.pwm.pin, self.npin)
+                }
+            }
+
+            impl<PIN: Pin, NPIN: PinN> PwmPin for ComplementaryPwm<$TIMx, PIN, NPIN> {
+                type Duty = u16;
+
+                fn disable(&mut self) {
+                    let flags = (CCE | CCNE) << PIN::CHANNEL.ccer_offset();
+                    unsafe { (*$TIMx::ptr()).ccer.modify(|r, w| w.bits(r.bits() & !flags)) };
+                }
+
+                fn enable(&mut self) {
+                    // Both outputs are enabled at once, so that dead-time is respected from the start.
+                    // MOE is set already, when channel is configured while its outputs are disabled.
+                    let flags = (CCE | CCNE) << PIN::CHANNEL.ccer_offset();
+                    unsafe { (*$TIMx::ptr()).ccer.modify(|r, w| w.bits(r.bits() | flags)) };
+                }
+
+                fn get_duty(&self) -> Self::Duty {
+                    self.pwm.get_duty()
+                }
+
+                fn get_max_duty(&self) -> Self::Duty {
+                    self.pwm.get_max_duty()
+                }
+
+                fn set_duty(&mut self, duty: Self::Duty) {
+                    self.pwm.set_duty(duty)
+                }
+            }
+        )+
+    }
+}
+
+impl_complementary_pwm!(TIM1 TIM8);
+
+macro_rules! impl_center_aligned_pwm {
+    ($($TIMx:ident)+) => {
+        $(
+            impl Timer<$TIMx> {
+                /// Switches PWM of timer's channels into center-aligned mode, keeping its frequency.
+                ///
+                /// As counter counts up to ARR and then down, ARR is halved and so is resolution of duty,
+                /// reported by `get_max_duty`. Therefore duty of channels needs to be set again.
+                ///
+                /// Update event is generated on both overflow and underflow, i.e. twice per PWM period,
+                /// which also affects `wait` and timeout interrupt. On TIM1 and TIM8 repetition counter
+                /// can be used to get single update event per period.
+                pub fn set_pwm_center_aligned(&mut self, mode: CenterAligned) {
+                    let is_edge_aligned = self.tim.cr1.read().cms().bits() == 0;
+
+                    self.set_center_aligned(mode);
+                    if is_edge_aligned {
+                        let arr = self.tim.arr.read().bits();
+                        self.tim.arr.write(|w| unsafe { w.bits(arr / 2) });
+                    }
+                }
+            }
+        )+
+    }
+}
+
+impl_center_aligned_pwm!(TIM1 TIM8 TIM2 TIM3 TIM4);
+
+impl_pwm!(
+    TIM1: [
+        idx: 1;
+        duty: u16;
+        bdtr: bdtr;
+        channels: [
+            CH1 => {ccmr: ccmr1_output; ccr: ccr1},
+            CH2 => {ccmr: ccmr1_output; ccr: ccr2},
+            CH3 => {ccmr: ccmr2_output; ccr: ccr3},
+            CH4 => {ccmr: ccmr2_output; ccr: ccr4},
+        ]
+    ]
+    TIM8: [
+        idx: 8;
+        duty: u16;
+        bdtr: bdtr;
+        channels: [
+            CH1 => {ccmr: ccmr1_output; ccr: ccr1},
+            CH2 => {ccmr: ccmr1_output; ccr: ccr2},
+            CH3 => {ccmr: ccmr2_output; ccr: ccr3},
+            CH4 => {ccmr: ccmr2_output; ccr: ccr4},
+        ]
+    ]
+    TIM2: [
+        idx: 2;
+        duty: u32;
+        channels: [
+            CH1 => {ccmr: ccmr1_output; ccr: ccr1},
+            CH2 => {ccmr: ccmr1_output; ccr: ccr2},
+            CH3 => {ccmr: ccmr2_output; ccr: ccr3},
+            CH4 => {ccmr: ccmr2_output; ccr: ccr4},
+        ]
+    ]
+    TIM3: [
+        idx: 3;
+        duty: u16;
+        channels: [
+            CH1 => {ccmr: ccmr1_output; ccr: ccr1},
+            CH2 => {ccmr: ccmr1_output; ccr: ccr2},
+            CH3 => {ccmr: ccmr2_output; ccr: ccr3},
+            CH4 => {ccmr: ccmr2_output; ccr: ccr4},
+        ]
+    ]
+    TIM4: [
+        idx: 4;
+        duty: u16;
+        channels: [
+            CH1 => {ccmr: ccmr1_output; ccr: ccr1},
+            CH2 => {ccmr: ccmr1_output; ccr: ccr2},
+            CH3 => {ccmr: ccmr2_output; ccr: ccr3},
+            CH4 => {ccmr: ccmr2_output; ccr: ccr4},
+        ]
+    ]
+    TIM15: [
+        idx: 15;
+        duty: u16;
+        bdtr: bdtr;
+        channels: [
+            CH1 => {ccmr: ccmr1_output; ccr: ccr1},
+        ]
+    ]
+    TIM16: [
+        idx: 16;
+        duty: u16;
+        bdtr: bdtr;
+        channels: [
+            CH1 => {ccmr: ccmr1_output; ccr: ccr1},
+        ]
+    ]
+    TIM17: [
+        idx: 17;
+        duty: u16;
+        bdtr: bdtr;
+        channels: [
+            CH1 => {ccmr: ccmr1_output; ccr: ccr1},
+        ]
+    ]
+);